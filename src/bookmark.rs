@@ -0,0 +1,128 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::util;
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+
+/// Directories bookmarked with m<letter>, saved as "<letter> <path>" lines, the path escaped
+/// with util::escape so every mark stays on its line.
+pub struct Bookmarks {
+    file: Option<PathBuf>,
+    pub marks: Vec<(char, PathBuf)>,
+}
+
+impl Bookmarks {
+    /// Read the bookmarks from file, no file means no bookmarks
+    pub fn load(file: Option<PathBuf>) -> Bookmarks {
+        let mut bookmarks = Bookmarks {
+            file,
+            marks: Vec::new(),
+        };
+        let content = match &bookmarks.file {
+            Some(file) => read_to_string(file).unwrap_or_default(),
+            None => String::new(),
+        };
+        for line in content.lines() {
+            let mut chars = line.chars();
+            let (Some(key), Some(' ')) = (chars.next(), chars.next()) else {
+                continue;
+            };
+            if key.is_ascii_alphabetic() {
+                bookmarks
+                    .marks
+                    .push((key, PathBuf::from(util::unescape(chars.as_str()))));
+            }
+        }
+        bookmarks.marks.sort_by_key(|(key, _)| *key);
+        bookmarks
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let mut content = String::new();
+        for (key, path) in self.marks.iter() {
+            content.push_str(&format!(
+                "{} {}\n",
+                key,
+                util::escape(&path.to_string_lossy())
+            ));
+        }
+        util::write_atomic(file, content.as_bytes())
+    }
+
+    pub fn get(&self, key: char) -> Option<&PathBuf> {
+        self.marks
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, path)| path)
+    }
+
+    /// Bookmark the path, replacing the old one with the same key
+    pub fn set(&mut self, key: char, path: PathBuf) {
+        match self.marks.binary_search_by_key(&key, |(k, _)| *k) {
+            Ok(i) => self.marks[i].1 = path,
+            Err(i) => self.marks.insert(i, (key, path)),
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.marks.len() {
+            self.marks.remove(index);
+        }
+    }
+
+    /// Lines for the bookmark list
+    pub fn items(&self) -> Vec<String> {
+        self.marks
+            .iter()
+            .map(|(key, path)| format!("{}  {}", key, path.to_string_lossy()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::random_dir_wcontent;
+
+    #[test]
+    fn test_bookmarks() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        let file = PathBuf::from(format!("/tmp/{}/state/bookmarks", root_dir));
+        let mut bookmarks = Bookmarks::load(Some(file.clone()));
+        assert!(bookmarks.marks.is_empty());
+
+        let a = PathBuf::from(format!("/tmp/{}/{}", root_dir, dirs[0]));
+        let b = PathBuf::from(format!("/tmp/{}/{} with space", root_dir, dirs[1]));
+        let c = PathBuf::from(format!("/tmp/{}/new\nline\\n back\\", root_dir));
+        bookmarks.set('c', c.clone());
+        bookmarks.set('b', b.clone());
+        bookmarks.set('a', b.clone());
+        bookmarks.set('a', a.clone());
+        bookmarks.save().expect("failed to save bookmarks");
+
+        let mut bookmarks = Bookmarks::load(Some(file.clone()));
+        assert_eq!(
+            bookmarks.marks,
+            [('a', a.clone()), ('b', b.clone()), ('c', c.clone())]
+        );
+        assert_eq!(bookmarks.get('b'), Some(&b));
+        assert_eq!(bookmarks.get('d'), None);
+
+        bookmarks.remove(2);
+        bookmarks.remove(0);
+        bookmarks.save().expect("failed to save bookmarks");
+        let bookmarks = Bookmarks::load(Some(file));
+        assert_eq!(bookmarks.marks, [('b', b)]);
+    }
+}
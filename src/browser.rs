@@ -6,52 +6,261 @@
 ║ above copyright notice and this permission notice appear in all copies ║
 ╚═══════════════════════════════════════════════════════════════════════*/
 
+use crate::bookmark::Bookmarks;
 use crate::canvas;
-use crate::ops::{consts, Mode, Op};
+use crate::event::{Events, Waker};
+use crate::find::Finder;
+use crate::frecency::Frecency;
+use crate::grep::{Grepper, Hit};
+use crate::history::History;
+use crate::input::{Key, KeyReader, Mouse, MouseKind};
+use crate::jumplist::{Jump, JumpList};
+use crate::line_editor::{self, Edit, LineEditor};
+use crate::list::ListView;
+use crate::load::{by_name, read_names, read_some_names, Loader};
+use crate::matcher;
+use crate::ops::{consts, DestFormat, Exit, Mode, Op, SearchMode};
+use crate::tab::{self, Entry, Tab};
 use crate::util;
-use regex_lite::RegexBuilder;
-use std::collections::VecDeque;
+use crate::watch::Watcher;
+use crate::work::{Job, Worker};
+use regex_lite::Regex;
+use std::collections::HashSet;
 use std::ffi::OsString;
-use std::fs::read_dir;
-use std::mem::take;
-use std::path::PathBuf;
+use std::fs::File;
+use std::mem::{replace, take};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use std::vec::Vec;
 
-const SEARCH_HISTORY_LEN: usize = 256;
+// editors that open a file at a line with '+<line>'
+const LINE_EDITORS: [&str; 7] = ["vi", "vim", "nvim", "nano", "emacs", "micro", "kak"];
+// lines scrolled by a turn of the mouse wheel
+const WHEEL_LINES: isize = 3;
+// the directories on the screen are read again when they've been quiet for SETTLE after a change,
+// or MAX_SETTLE after the first change if they keep changing
+const SETTLE: Duration = Duration::from_millis(200);
+const MAX_SETTLE: Duration = Duration::from_secs(1);
+// names read for the preview, it says it's truncated when there are more
+const PREVIEW_LIMIT: usize = 1000;
+// how long a directory is waited for before it's listed as it's read
+const LOAD_WAIT: Duration = Duration::from_millis(50);
 
 struct Opener {
     comm: OsString,
     args: Vec<OsString>,
+    // opener is started in the background and trans keeps running, e.g. 'o = code &'
+    detach: bool,
+}
+
+/// Pick files or directories instead of changing directory, see --choose-files and --choose-dir
+pub struct Chooser {
+    out: File,
+    dirs: bool,
+    nul: bool,
+}
+
+/// The search text compiled, it's compiled again only when the text or the mode changes
+struct CompiledSearch {
+    txt: Vec<char>,
+    mode: SearchMode,
+    // None if it's not a valid pattern
+    re: Option<Regex>,
+}
+
+/// Entries narrowed down by the filter prompt, content only has the matching ones while it's on
+pub struct Filter {
+    // fzf style fuzzy match, or regex like the search
+    pub fuzzy: bool,
+    // everything in the directory, or in the tree
+    pub all: Vec<Entry>,
+    // indices of the matched characters of each entry in content
+    pub positions: Vec<Vec<usize>>,
+    // the score of each entry in content, to merge in what's read later
+    pub scores: Vec<i64>,
+}
+
+/// What a paste did: the name of the first path pasted, every failure, and whether it was
+/// cancelled
+#[derive(Default)]
+struct Pasted {
+    first: Option<String>,
+    errors: Vec<String>,
+    cancelled: bool,
+}
+
+/// What a key did to the text in the bottom bar prompt
+enum PromptKey {
+    Esc,
+    Enter,
+    Up,
+    Down,
+    // Ctrl + R, switches the match mode
+    Mode,
+    Edited,
+    Ignored,
+}
+
+/// The names for the preview of the entry at path, none if it isn't a directory. No more than
+/// PREVIEW_LIMIT are read, and whether there are more.
+fn preview_of(path: Option<PathBuf>) -> (Vec<String>, bool) {
+    match path {
+        Some(dir) if dir.is_dir() => read_some_names(&dir, PREVIEW_LIMIT),
+        _ => (Vec::new(), false),
+    }
+}
+
+/// A preview read by the previewer, with the path it's of
+type Preview = (Option<PathBuf>, (Vec<String>, bool));
+
+/// The filter's hits in the tree view, in order and with the directories they're in so no entry
+/// is drawn without its parent. The directories have nothing highlighted and score nothing.
+fn with_ancestors(
+    all: &[Entry],
+    hits: Vec<(i64, usize, Vec<usize>)>,
+) -> Vec<(i64, usize, Vec<usize>)> {
+    let mut hits = hits.into_iter().peekable();
+    let mut kept = Vec::new();
+    // the entry at each depth above the current one, and whether it's kept already
+    let mut above: Vec<(usize, bool)> = Vec::new();
+    for (i, entry) in all.iter().enumerate() {
+        above.truncate(entry.depth);
+        match hits.next_if(|hit| hit.1 == i) {
+            Some(hit) => {
+                for (dir, shown) in above.iter_mut().filter(|(_, shown)| !*shown) {
+                    kept.push((0, *dir, Vec::new()));
+                    *shown = true;
+                }
+                kept.push(hit);
+                above.push((i, true));
+            }
+            None => above.push((i, false)),
+        }
+    }
+    kept
+}
+
+/// Copy or move the paths into the directory, a name that's taken gets a '_' appended until it
+/// isn't. A path moved into the directory it's in stays as it is. It stops when cancel is set,
+/// what it was on is removed from the directory if it's half done.
+fn paste_paths(paths: &[PathBuf], dir: &Path, cut: bool, cancel: &AtomicBool) -> Pasted {
+    let mut pasted = Pasted::default();
+    for path in paths {
+        if cancel.load(Ordering::Relaxed) {
+            pasted.cancelled = true;
+            break;
+        }
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        if cut && path.parent() == Some(dir) {
+            pasted
+                .first
+                .get_or_insert(name.to_string_lossy().to_string());
+            continue;
+        }
+        let mut name = name.to_os_string();
+        while dir.join(&name).symlink_metadata().is_ok() {
+            name.push("_");
+        }
+        let dest = dir.join(&name);
+        let result = if cut {
+            util::move_path(path, &dest, cancel)
+        } else {
+            util::copy_path(path, &dest, cancel)
+        };
+        match result {
+            Ok(()) => {
+                pasted
+                    .first
+                    .get_or_insert(name.to_string_lossy().to_string());
+            }
+            Err(_) if cancel.load(Ordering::Relaxed) => {
+                let _ = util::remove_path(&dest);
+                pasted.cancelled = true;
+                break;
+            }
+            Err(e) => pasted
+                .errors
+                .push(format!("Failed to paste {}: {}", path.display(), e)),
+        }
+    }
+    pasted
 }
 
 /// Directory browser
 pub struct Browser {
-    pub cursor: usize,
-    pub window_start: usize,
-    pub content: Vec<String>, // String instead of PathBuf for display purposes
-    past_dir: Vec<PathBuf>,
-    past_cursor: Vec<usize>,
-    past_window_start: Vec<usize>,
-    pub current_path: PathBuf,
+    // all the tabs, tab_index is the one being shown
+    pub tabs: Vec<Tab>,
+    pub tab_index: usize,
+    // yy and dd, pasted with pp in any tab
+    copied: Vec<PathBuf>,
+    cut: bool,
+    // the dual-pane mode, the tab shown side by side with the current one
+    pub pane: Option<usize>,
     original_path: PathBuf,
     pub mode: Mode,
     pub search_txt: Vec<char>,
+    // regex, literal or glob, Ctrl + R in the search prompt switches
+    pub search_mode: SearchMode,
+    compiled_search: CompiledSearch,
     opener_o: Opener,
     opener_enter: Opener,
     dest_file: Option<PathBuf>,
-    search_history: VecDeque<Vec<char>>,
-    search_history_index: usize,
-    trunc: Vec<u8>,
+    dest_format: DestFormat,
+    search_history: History,
+    keys: KeyReader,
+    // a count and the first key of a two key command typed so far, e.g. 5 of 5j or g of gg
+    pending: util::Pending,
+    line_editor: LineEditor,
     pub input_cursor_pos: usize,
     rev_search: bool,
-    pub preview: Vec<String>,
-    refresh_preview: bool,
+    chooser: Option<Chooser>,
+    bookmarks: Bookmarks,
+    jumplist: JumpList,
+    frecency: Frecency,
+    frecency_txt: Vec<char>,
+    // directories matching frecency_txt, in the order of the list
+    frecent: Vec<PathBuf>,
+    // lines kept in the window above and below the cursor, see 'scrolloff' in the config file
+    scrolloff: usize,
+    // inotify watches on the directories on the screen, None without inotify
+    watcher: Option<Watcher>,
+    // the first and the last change in the watched directories since they were read
+    changes: Option<(Instant, Instant)>,
+    // what the event loop waits on besides the keys, None until it starts
+    events: Option<Events>,
+    // reads the preview of the entry under the cursor, started with the event loop
+    previewer: Option<Worker<Option<PathBuf>, Preview>>,
+    // a paste in progress and the directory it's pasting into, Esc cancels it
+    paste_job: Option<(PathBuf, Job<Pasted>)>,
+    // q or i while pasting asks first, the next key says
+    quit_asked: bool,
+    finder: Option<Finder>,
+    find_txt: Vec<char>,
+    pub find_fuzzy: bool,
+    // the scores of the found paths in the list, and how many of them are ranked
+    found_scores: Vec<i64>,
+    found_ranked: usize,
+    grepper: Option<Grepper>,
+    grep_txt: Vec<char>,
+    pub list: ListView,
+    // shown in the bottom bar in place of the current path until the next key
+    pub message: Option<String>,
 }
 
 #[cfg(test)]
 const TEST_HEIGHT: usize = 10;
 
+/// Whether the tabs are listed in the first line of a terminal this high. There's no tab bar with
+/// only one tab, or when it would leave no room for the listing.
+pub fn has_tab_bar(tabs: usize, term_height: usize) -> bool {
+    tabs > 1 && term_height > 2
+}
+
 // both attributes are for h
 #[allow(unused_mut)]
 #[allow(unused_assignments)]
@@ -71,104 +280,201 @@ fn get_height() -> usize {
 impl Browser {
     /// Construct past directory stack according to the current path
     pub fn init(&mut self, path: &str) {
-        self.read_content(path);
+        self.tab_mut().filter = None;
+
+        self.tab_mut().past_dir.clear();
+        self.tab_mut().past_cursor.clear();
+        self.tab_mut().past_window_start.clear();
 
         let mut srcdir = PathBuf::from(path)
             .canonicalize()
             .expect("Failed to canonicalize current directory");
 
         loop {
-            self.past_dir.push(srcdir.clone());
-            self.past_cursor.push(0);
-            self.past_window_start.push(0);
+            self.tab_mut().past_dir.push(srcdir.clone());
+            self.tab_mut().past_cursor.push(0);
+            self.tab_mut().past_window_start.push(0);
 
             if !srcdir.pop() {
                 break;
             }
         }
 
-        self.past_dir.reverse();
+        self.tab_mut().past_dir.reverse();
 
-        if self.past_dir.len() >= 1 {
-            self.current_path = self
+        if self.tab().past_dir.len() >= 1 {
+            self.tab_mut().current_path = self
+                .tab_mut()
                 .past_dir
                 .pop()
                 .expect("Failed to pop the last element from past_dir")
                 .clone();
 
-            self.past_cursor
+            self.tab_mut()
+                .past_cursor
                 .pop()
                 .expect("Failed to pop from past_cursor");
 
-            self.past_window_start
+            self.tab_mut()
+                .past_window_start
                 .pop()
                 .expect("Failed to pop from past_window_start");
         }
 
+        let current_path = self.tab().current_path.to_string_lossy().to_string();
+        self.read_content(&current_path);
         self.top();
     }
 
-    fn cursor_add_one(&mut self) -> usize {
-        if self.cursor + 1 < self.content.len() {
-            self.cursor + 1
-        } else {
-            0
-        }
-    }
-
-    fn cursor_minus_one(&mut self) -> usize {
-        if self.cursor as isize - 1 >= 0 {
-            self.cursor - 1
-        } else {
-            self.content.len() - 1
+    /// The entry n entries away from the cursor, past either end it wraps around
+    fn cursor_moved(&self, n: isize) -> usize {
+        if self.tab().content.is_empty() {
+            return 0;
         }
+        (self.tab().cursor as isize + n).rem_euclid(self.tab().content.len() as isize) as usize
     }
 
     /// Window display update loop
     pub fn start_loop(&mut self, canvas: &mut canvas::Canvas) {
+        self.watcher = Watcher::new();
+        self.events = Events::new().ok();
+        // what's still being read was started before there was anything to wake the loop up
+        for i in 0..self.tabs.len() {
+            if let Some(loader) = self.tabs[i].loader.take() {
+                self.in_tab(i, |b| {
+                    let dir = b.tab().current_path.clone();
+                    // a listing shown as it's read is read again from the start
+                    if loader.shown {
+                        b.tab_mut().content.clear();
+                    }
+                    b.load(dir, loader.shown);
+                    if let Some(restarted) = b.tab_mut().loader.as_mut() {
+                        restarted.select = loader.select;
+                    }
+                });
+            }
+        }
         loop {
-            if self.refresh_preview {
-                self.refresh_preview = false;
-                self.preview = self.get_preview();
+            if self.tab().refresh_preview {
+                self.tab_mut().refresh_preview = false;
+                self.update_preview();
+            }
+            if self.paste_job.is_some() && self.message.is_none() {
+                self.message = Some(String::from("Pasting…  esc cancels"));
+            }
+            if canvas.has_parent_column() {
+                self.update_parent();
             }
+            self.update_search_matches();
 
             canvas.draw(self, None);
 
-            if matches!(self.mode, Mode::Search) || matches!(self.mode, Mode::RevSearch) {
-                self.search(canvas);
+            if !self.wait_event() {
                 continue;
             }
-            match util::process_input() {
-                Op::Up => {
-                    self.up();
+            let key = self.keys.read_key();
+            // there won't be any more keys, quitting still waits for the paste
+            if key == Key::Closed {
+                self.quit();
+            }
+            // the prompts and the grep results take the key themselves
+            match self.mode {
+                Mode::Normal | Mode::Bookmarks => {}
+                Mode::Search | Mode::RevSearch => {
+                    self.search(canvas, key);
+                    continue;
+                }
+                Mode::Frecency => {
+                    self.frecency_prompt(canvas, key);
+                    continue;
                 }
-                Op::Down => {
-                    self.down();
+                Mode::Filter => {
+                    self.filter_prompt(canvas, key);
+                    continue;
                 }
-                Op::Left => {
-                    self.left();
+                Mode::Find => {
+                    self.find_prompt(canvas, key);
+                    continue;
                 }
-                Op::Right => {
-                    self.right();
+                Mode::Grep => {
+                    self.grep_prompt(canvas, key);
+                    continue;
                 }
+                Mode::GrepResults => {
+                    self.grep_results(key);
+                    continue;
+                }
+            }
+            let (op, count) = match key {
+                Key::Mouse(mouse) if matches!(self.mode, Mode::Normal) => {
+                    (self.mouse(mouse, canvas), None)
+                }
+                // d deletes a bookmark, it's a prefix for dd otherwise
+                Key::Char('d') if matches!(self.mode, Mode::Bookmarks) => (Op::Delete, None),
+                // Tab is Ctrl + I otherwise
+                Key::Tab | Key::BackTab if self.pane.is_some() => (Op::SwitchPane, None),
+                key => util::process_input(key, &mut self.pending),
+            };
+            self.message = None;
+            if matches!(self.mode, Mode::Bookmarks) {
+                self.bookmark_list(op);
+                continue;
+            }
+            // a count is a line number for gg and G, zt, zz and zb, a tab number for gt, how many
+            // times otherwise
+            let nth = count.map(|count| count.saturating_sub(1));
+            let line = count.map(|count| count.min(self.tab().content.len()).saturating_sub(1));
+            let count = count.unwrap_or(1).max(1);
+            let quit_asked = take(&mut self.quit_asked);
+            match op {
+                // quitting waits for the paste to finish, it's asked first
+                Op::Exit | Op::Quit if self.paste_job.is_some() && !quit_asked => {
+                    self.quit_asked = true;
+                    self.message = Some(String::from(
+                        "Still pasting, again to quit once it's done, esc cancels",
+                    ));
+                }
+                Op::Up => self.move_by(-(count as isize)),
+                Op::Down => self.move_by(count as isize),
+                Op::Left if self.tab().tree => self.collapse(),
+                Op::Right if self.tab().tree => self.expand(),
+                Op::Left => self.leave_dirs(count),
+                Op::Right => self.enter_dirs(count),
                 Op::ExitCursorO => {
                     self.exit_under_cursor(Op::ExitCursorO);
                 }
                 Op::ExitCursorEnter => {
-                    self.exit_under_cursor(Op::ExitCursorEnter);
+                    if self.chooser.is_some() {
+                        self.choose_under_cursor();
+                    } else {
+                        self.exit_under_cursor(Op::ExitCursorEnter);
+                    }
                 }
                 Op::Exit => {
+                    if self.chooser.as_ref().is_some_and(|c| c.dirs) {
+                        self.choose(vec![self.tab().current_path.clone()]);
+                    }
                     self.exit_cur_dir();
                 }
                 Op::Quit => {
                     self.quit();
                 }
+                Op::Top | Op::Bottom if line.is_some() => self.move_cursor(line.unwrap_or(0)),
                 Op::Top => {
                     self.top();
                 }
                 Op::Bottom => {
                     self.bottom();
                 }
+                Op::ScreenTop | Op::ScreenMiddle | Op::ScreenBottom => self.screen_jump(op, count),
+                Op::ScrollTop | Op::ScrollCenter | Op::ScrollBottom => {
+                    if let Some(line) = line {
+                        self.move_cursor(line);
+                    }
+                    self.scroll_cursor(op);
+                }
+                Op::ScrollDown => self.scroll(count as isize),
+                Op::ScrollUp => self.scroll(-(count as isize)),
                 Op::Search => {
                     self.search_txt = Vec::new();
                     self.mode = Mode::Search;
@@ -180,23 +486,97 @@ impl Browser {
                     self.rev_search = true;
                 }
                 Op::NextMatch => {
-                    let start = if self.rev_search {
-                        self.cursor_minus_one()
-                    } else {
-                        self.cursor_add_one()
-                    };
-                    self.next_match(start, false);
+                    for _ in 0..count {
+                        let start = self.cursor_moved(if self.rev_search { -1 } else { 1 });
+                        self.next_match(start, false);
+                    }
                 }
                 Op::PrevMatch => {
-                    let start = if self.rev_search {
-                        self.cursor_add_one()
-                    } else {
-                        self.cursor_minus_one()
-                    };
-                    self.next_match(start, true);
+                    for _ in 0..count {
+                        let start = self.cursor_moved(if self.rev_search { 1 } else { -1 });
+                        self.next_match(start, true);
+                    }
+                }
+                Op::PageUp => (0..count).for_each(|_| self.pageup()),
+                Op::PageDown => (0..count).for_each(|_| self.pagedown()),
+                Op::FullPageUp => self.page(-(count as isize)),
+                Op::FullPageDown => self.page(count as isize),
+                Op::FindChar(c) => self.find_char(c, count),
+                Op::Mark => self.toggle_mark(),
+                Op::SetBookmark(key) => self.set_bookmark(key),
+                Op::GotoBookmark(key) => match self.bookmarks.get(key).cloned() {
+                    Some(path) => self.jump(&path),
+                    None => self.message = Some(format!("No bookmark '{}'", key)),
+                },
+                Op::NewTab => self.new_tab(),
+                Op::CloseTab => self.close_tab(),
+                Op::NextTab => match nth {
+                    Some(index) => self.switch_tab(index),
+                    None => self.switch_tab((self.tab_index + 1) % self.tabs.len()),
+                },
+                Op::PrevTab => {
+                    let len = self.tabs.len();
+                    self.switch_tab((self.tab_index + len - count % len) % len);
+                }
+                Op::Copy => self.copy(false),
+                Op::Cut => self.copy(true),
+                Op::Paste => self.paste(),
+                Op::Cancel if self.paste_job.is_some() => self.cancel_paste(),
+                Op::Tree => self.toggle_tree(),
+                Op::Reload => self.reload_panes(),
+                Op::DualPane => self.toggle_dual(),
+                Op::SwitchPane => {
+                    if let Some(other) = self.pane {
+                        self.switch_tab(other);
+                    }
+                }
+                Op::CopyToOther => self.paste_to_other(false),
+                Op::MoveToOther => self.paste_to_other(true),
+                Op::JumpBack => self.jump_back(),
+                Op::JumpForward => self.jump_forward(),
+                Op::Bookmarks => {
+                    self.list = ListView::new(
+                        "bookmarks  <enter> jump  d delete  q close",
+                        self.bookmarks.items(),
+                    );
+                    self.mode = Mode::Bookmarks;
+                }
+                Op::Frecency => {
+                    self.frecency_txt = Vec::new();
+                    self.input_cursor_pos = 0;
+                    self.mode = Mode::Frecency;
+                    self.update_frecent();
+                }
+                Op::Filter => {
+                    if self.tab().filter.is_none() {
+                        self.tab_mut().filter_txt = Vec::new();
+                        let all = self.tab().content.clone();
+                        self.tab_mut().filter = Some(Filter {
+                            fuzzy: true,
+                            positions: vec![Vec::new(); all.len()],
+                            scores: vec![0; all.len()],
+                            all,
+                        });
+                    }
+                    self.input_cursor_pos = self.tab().filter_txt.len();
+                    self.mode = Mode::Filter;
+                }
+                Op::ClearFilter => self.clear_filter(),
+                Op::Find => {
+                    self.finder =
+                        Some(Finder::start(self.tab().current_path.clone(), self.waker()));
+                    self.find_txt = Vec::new();
+                    self.input_cursor_pos = 0;
+                    self.list = ListView::default();
+                    self.found_scores.clear();
+                    self.found_ranked = 0;
+                    self.mode = Mode::Find;
+                }
+                Op::Grep => {
+                    self.grep_txt = Vec::new();
+                    self.input_cursor_pos = 0;
+                    self.mode = Mode::Grep;
                 }
-                Op::PageUp => self.pageup(),
-                Op::PageDown => self.pagedown(),
                 _ => {
                     continue;
                 }
@@ -204,72 +584,202 @@ impl Browser {
         }
     }
 
-    ///  Get directory content preview window
-    fn get_preview(&self) -> Vec<String> {
-        let empty: Vec<String> = Vec::new();
+    /// The entry under the cursor, which the preview shows
+    fn preview_path(&self) -> Option<PathBuf> {
+        let entry = self.tab().content.get(self.tab().cursor)?;
+        Some(self.tab().current_path.join(&*entry.path))
+    }
 
-        if self.content.len() == 0 {
-            return empty;
+    /// Ask the previewer for the preview, it's drawn when it's read. The one before stays on the
+    /// screen until then, which is only noticed when it's slow, e.g. on NFS.
+    fn update_preview(&mut self) {
+        let path = self.preview_path();
+        self.tab_mut().preview_dir = None;
+        if self.previewer.is_none() {
+            let waker = self.waker();
+            self.previewer = Some(Worker::start(waker, |path: Option<PathBuf>| {
+                let preview = preview_of(path.clone());
+                (path, preview)
+            }));
+        }
+        if let Some(previewer) = self.previewer.as_mut() {
+            previewer.request(path);
         }
+    }
 
-        let mut _dir = self.current_path.clone();
-        _dir.push(&self.content[self.cursor]);
-        if _dir.is_dir() == false {
-            return empty;
+    /// Show the preview that's been read, unless the cursor has moved on meanwhile
+    fn previewed(&mut self, (path, (preview, truncated)): Preview) {
+        if path != self.preview_path() {
+            return;
         }
-        let dir = _dir.to_str().expect("Failed to construct preview path");
+        let tab = self.tab_mut();
+        tab.preview = preview;
+        tab.preview_truncated = truncated;
+        tab.preview_dir = path.filter(|path| path.is_dir());
+    }
 
-        let mut preview = match read_dir(&dir) {
-            Ok(entries) => entries
-                .map(|_e| match _e {
-                    Ok(e) => match e.file_name().into_string() {
-                        Ok(filename) => filename,
-                        Err(filename_os) => filename_os.to_string_lossy().to_string(),
-                    },
-                    Err(_) => String::new(),
-                })
-                .collect::<Vec<String>>(),
-            Err(_) => Vec::new(),
-        };
+    /// Read the parent directory for the parent column, when the current directory changed
+    fn update_parent(&mut self) {
+        let tab = self.tab_mut();
+        if tab.parent_of.as_ref() == Some(&tab.current_path) {
+            return;
+        }
+        tab.parent_of = Some(tab.current_path.clone());
+        match (tab.current_path.parent(), tab.current_path.file_name()) {
+            (Some(parent), Some(name)) => {
+                tab.parent = read_names(parent);
+                tab.parent_cursor = tab.parent.iter().position(|entry| name == entry.as_str());
+            }
+            _ => {
+                tab.parent = Vec::new();
+                tab.parent_cursor = None;
+            }
+        }
+    }
 
-        preview.sort_by(|d1, d2| d1.to_lowercase().cmp(&d2.to_lowercase()));
-        preview
+    /// Where the parent column starts, the current directory is in the middle of it if it can be
+    pub fn parent_window_start(&self) -> usize {
+        let height = self.height();
+        self.tab()
+            .parent_cursor
+            .unwrap_or(0)
+            .saturating_sub(height / 2)
+            .min(self.tab().parent.len().saturating_sub(height))
     }
 
     /// set cursor position, centered in the window
     fn set_cursor_pos_centered(&mut self, index: usize) {
         // the bottom line will always be there and cover it, the max display height is always
         // terminal height - 1
-        let h = get_height();
-        self.cursor = index;
-        self.window_start = if self.cursor as isize - h as isize / 2 > 0 {
-            self.cursor - h / 2
+        let h = self.height();
+        self.tab_mut().cursor = index;
+        self.tab_mut().window_start = if self.tab().cursor as isize - h as isize / 2 > 0 {
+            self.tab().cursor - h / 2
         } else {
             0
         };
     }
 
-    fn save_history(&mut self) {
-        if self.search_history.len() >= SEARCH_HISTORY_LEN {
-            self.search_history.pop_front();
+    /// Go to a directory anywhere, the past directory stack is rebuilt as if trans was started
+    /// there so h still works
+    fn jump(&mut self, path: &Path) {
+        match path.to_str() {
+            Some(dir) if path.is_dir() => {
+                self.jumplist.leave(&self.cursor_name());
+                self.init(dir);
+                self.visited();
+            }
+            _ => self.message = Some(format!("{} is not a directory", path.to_string_lossy())),
         }
-        // don't save an empty line
-        if self.search_txt.len() == 0 {
-            return;
+    }
+
+    /// Go back to the previous directory in the jumplist
+    fn jump_back(&mut self) {
+        self.jumplist.leave(&self.cursor_name());
+        if let Some(jump) = self.jumplist.back() {
+            self.goto_jump(jump);
         }
-        if self.search_history_index < self.search_history.len() {
-            let string_a = self.search_history[self.search_history_index]
-                .clone()
-                .into_iter()
-                .collect::<String>();
-            let string_b = self.search_txt.clone().into_iter().collect::<String>();
-            if string_a == string_b {
-                // history could be modified by user, that case we save both instead of overwriting the old
-                self.search_history.remove(self.search_history_index);
+    }
+
+    fn jump_forward(&mut self) {
+        self.jumplist.leave(&self.cursor_name());
+        if let Some(jump) = self.jumplist.forward() {
+            self.goto_jump(jump);
+        }
+    }
+
+    /// Go to a directory in the jumplist, with the cursor where it was
+    fn goto_jump(&mut self, jump: Jump) {
+        match jump.path.to_str() {
+            Some(dir) if jump.path.is_dir() => {
+                self.init(dir);
+                self.frecency.add(&self.tabs[self.tab_index].current_path);
+                self.select(&jump.cursor);
+            }
+            _ => {
+                self.message = Some(format!(
+                    "{} is not a directory",
+                    jump.path.to_string_lossy()
+                ))
             }
         }
-        self.search_history.push_back(self.search_txt.clone());
-        self.search_history_index = self.search_history.len(); // out-of-bound on purpose
+    }
+
+    /// current_path is changed, it goes to the jumplist and the frecency database
+    fn visited(&mut self) {
+        let path = &self.tabs[self.tab_index].current_path;
+        self.jumplist.visit(path.clone());
+        self.frecency.add(path);
+    }
+
+    /// Name of the entry under the cursor, empty if there's none
+    fn cursor_name(&self) -> String {
+        self.tab().cursor_name()
+    }
+
+    fn set_bookmark(&mut self, key: char) {
+        self.bookmarks.set(key, self.tab().current_path.clone());
+        self.message = match self.bookmarks.save() {
+            Ok(_) => Some(format!("Bookmarked as '{}'", key)),
+            Err(e) => Some(format!("Failed to save bookmarks: {}", e)),
+        };
+    }
+
+    /// Keys in the bookmark list
+    fn bookmark_list(&mut self, op: Op) {
+        let height = self.height();
+        match op {
+            Op::Up => self.list.up(height),
+            Op::Down => self.list.down(height),
+            Op::Top => self.list.top(),
+            Op::Bottom => self.list.bottom(height),
+            Op::PageUp => self.list.pageup(height),
+            Op::PageDown => self.list.pagedown(height),
+            Op::Delete => {
+                self.bookmarks.remove(self.list.cursor);
+                self.list.remove(height);
+                if let Err(e) = self.bookmarks.save() {
+                    self.message = Some(format!("Failed to save bookmarks: {}", e));
+                }
+            }
+            Op::ExitCursorEnter | Op::ExitCursorO | Op::Right => {
+                self.mode = Mode::Normal;
+                if let Some((_, path)) = self.bookmarks.marks.get(self.list.cursor) {
+                    self.jump(&path.clone());
+                }
+            }
+            Op::Quit | Op::Left | Op::Cancel => self.mode = Mode::Normal,
+            _ => {}
+        }
+    }
+
+    /// Put the cursor on the entry with this name, if there is one
+    pub fn set_cursor_on(&mut self, name: &str) {
+        if let Some(i) = self
+            .tab()
+            .content
+            .iter()
+            .position(|entry| &*entry.path == name)
+        {
+            self.set_cursor_pos_centered(i);
+            self.tab_mut().refresh_preview = true;
+        }
+    }
+
+    /// Put the cursor on the entry, or on it once it's read if the directory is still being read
+    pub fn select(&mut self, name: &str) {
+        self.set_cursor_on(name);
+        let cursor = self.tab().cursor;
+        if let Some(loader) = self.tab_mut().loader.as_mut() {
+            loader.select = Some((name.to_string(), cursor));
+        }
+    }
+
+    fn save_history(&mut self) {
+        let search = self.search_txt.iter().collect::<String>();
+        if let Err(e) = self.search_history.add(&search) {
+            self.message = Some(format!("Failed to save search history: {}", e));
+        }
     }
 
     /// Next search match, can be a reversed search
@@ -278,357 +788,1452 @@ impl Browser {
             return;
         }
 
-        self.refresh_preview = true;
+        self.tab_mut().refresh_preview = true;
 
         if matches!(self.mode, Mode::RevSearch) || self.rev_search == true {
             rev = !rev;
         }
 
-        let mut matched = false;
-        let mut case_insensitive = true;
-
-        if self.cursor >= self.content.len() {
+        if self.tab().cursor >= self.tab().content.len() {
             return;
         }
 
-        let mut search: String = self.search_txt.iter().collect::<String>();
+        // Regex can be invalid while the user is typing
+        self.compile_search();
+        let re = match &self.compiled_search.re {
+            Some(re) => re,
+            None => return,
+        };
 
-        // Check if the case sensitive '\C' is present at the bottom of the search text
-        let len = self.search_txt.iter().count();
+        let len = self.tab().content.len();
+        // Didn't match? Start over
+        let found = if rev == false {
+            (start..len)
+                .chain(0..start)
+                .find(|i| re.is_match(self.tab().content[*i].name()))
+        } else {
+            (0..start + 1)
+                .rev()
+                .chain((start + 1..len).rev())
+                .find(|i| re.is_match(self.tab().content[*i].name()))
+        };
+        if let Some(i) = found {
+            self.set_cursor_pos_centered(i);
+        }
+    }
 
-        if len > 2 {
-            let last_two = self
-                .search_txt
-                .iter()
-                .skip(len - 2)
-                .take(2)
-                .collect::<String>();
-            if last_two.eq("\\C") {
-                let mut cnt = 0;
-                for c in self.search_txt.iter().rev().skip(2) {
-                    if *c == '\\' {
-                        cnt += 1;
-                    } else {
-                        break;
-                    }
-                }
-                // U\\\\\C is case sensitive
-                if cnt % 2 == 0 {
-                    search = self.search_txt.iter().take(len - 2).collect::<String>();
-                    case_insensitive = false;
-                }
+    /// Compile search_txt unless it's already compiled
+    pub fn compile_search(&mut self) {
+        if !self.is_compiled() {
+            self.compiled_search = CompiledSearch {
+                txt: self.search_txt.clone(),
+                mode: self.search_mode,
+                re: matcher::search_regex(&self.search_txt, self.search_mode).ok(),
+            };
+            // every tab's matches are of the old search
+            for tab in self.tabs.iter_mut() {
+                tab.search_matches = None;
             }
         }
+    }
 
-        // Regex can be invalid while the user is typing
-        let re = match RegexBuilder::new(&search)
-            .case_insensitive(case_insensitive)
-            .build()
-        {
-            Ok(re) => re,
-            Err(_) => RegexBuilder::new("^$")
-                .build()
-                .expect("Failed to parse regex for ^$"),
-        };
+    /// Look for the entries matching the search again if the content or the search changed, so
+    /// the counts don't go through the whole listing on every draw
+    fn update_search_matches(&mut self) {
+        if self.tab().search_matches.is_none() && self.search_re().is_some() {
+            self.tab_mut().search_matches = Some(self.find_search_matches());
+        }
+    }
 
-        if rev == false {
-            let ra = start..self.content.len();
-            for i in ra {
-                if re.is_match(&self.content[i]) {
-                    self.set_cursor_pos_centered(i);
-                    matched = true;
-                    break;
-                }
-            }
-        } else {
-            let ra = (0..start + 1).rev();
-            for i in ra {
-                if re.is_match(&self.content[i]) {
-                    self.set_cursor_pos_centered(i);
-                    matched = true;
-                    break;
-                }
-            }
+    /// Indices of the entries matching the search
+    fn find_search_matches(&self) -> Vec<usize> {
+        let Some(re) = self.search_re() else {
+            return Vec::new();
         };
+        (0..self.tab().content.len())
+            .filter(|i| re.is_match(self.tab().content[*i].name()))
+            .collect()
+    }
 
-        // Didn't match? Start over
-        if matched == false {
-            if rev == false {
-                let ra = 0..start;
-                for i in ra {
-                    if re.is_match(&self.content[i]) {
-                        self.set_cursor_pos_centered(i);
-                        break;
-                    }
-                }
-            } else {
-                let ra = (start + 1..self.content.len()).rev();
-                for i in ra {
-                    if re.is_match(&self.content[i]) {
-                        self.set_cursor_pos_centered(i);
-                        break;
-                    }
-                }
-            };
+    fn is_compiled(&self) -> bool {
+        self.compiled_search.txt == self.search_txt && self.compiled_search.mode == self.search_mode
+    }
+
+    /// The regex of the current search, None when there's no search or it's not valid
+    pub fn search_re(&self) -> Option<&Regex> {
+        if self.search_txt.is_empty() || !self.is_compiled() {
+            return None;
         }
+        self.compiled_search.re.as_ref()
     }
 
-    fn search(&mut self, canvas: &mut canvas::Canvas) {
-        let (_chars, trunc, op) = util::read_chars_or_op(&self.trunc);
-        self.trunc = trunc;
-        // regular text input
-        if op == Op::Noop && _chars.is_some() {
-            let mut chars = _chars.expect("unwrap char vec failed");
-            let first_char = chars[0] as usize;
-            // for example, Ctrl + C = 3, Ctrl + I = 9 these characters cannot be displayed, yet
-            // they will take space in the search text
-            if first_char < 32 {
-                // escape or return (line feed)
-                if first_char != 27 && first_char != 10 {
-                    return;
-                }
+    /// The search text is compiled and it's not a valid pattern
+    pub fn search_invalid(&self) -> bool {
+        !self.search_txt.is_empty() && self.is_compiled() && self.compiled_search.re.is_none()
+    }
+
+    /// Character positions of the search matches in the i-th entry, None if it doesn't match
+    pub fn search_positions(&self, i: usize) -> Option<Vec<usize>> {
+        matcher::regex_match(self.search_re()?, self.tab().content.get(i)?.name())
+    }
+
+    /// Text being typed in the bottom bar
+    pub fn prompt_txt(&self) -> &Vec<char> {
+        match self.mode {
+            Mode::Frecency => &self.frecency_txt,
+            Mode::Filter => &self.tab().filter_txt,
+            Mode::Find => &self.find_txt,
+            Mode::Grep => &self.grep_txt,
+            _ => &self.search_txt,
+        }
+    }
+
+    fn prompt_txt_mut(&mut self) -> &mut Vec<char> {
+        match self.mode {
+            Mode::Frecency => &mut self.frecency_txt,
+            Mode::Filter => &mut self.tab_mut().filter_txt,
+            Mode::Find => &mut self.find_txt,
+            Mode::Grep => &mut self.grep_txt,
+            _ => &mut self.search_txt,
+        }
+    }
+
+    /// Edit the prompt text in the bottom bar with a key, the rest is left to the caller
+    fn read_prompt(&mut self, canvas: &mut canvas::Canvas, key: Key) -> PromptKey {
+        let edit = match key {
+            Key::Enter => return PromptKey::Enter,
+            Key::Esc => return PromptKey::Esc,
+            Key::Up | Key::Ctrl('p') => return PromptKey::Up,
+            Key::Down | Key::Ctrl('n') => return PromptKey::Down,
+            Key::Ctrl('r') => return PromptKey::Mode,
+            key => match line_editor::key_edit(&key) {
+                Some(edit) => edit,
+                None => return PromptKey::Ignored,
+            },
+        };
+        if edit == Edit::Backspace && self.input_cursor_pos > 0 {
+            // this is added so user knows what's being deleted.
+            // there could be a problem when the lengths of the UTF-8 characters (the one
+            // being deleted and the new one added on the left for alignment) are not equal,
+            // making the cursor all over the place.
+            if canvas.bottom_start > 0 {
+                canvas.bottom_start -= 1;
             }
-            if first_char == 27 {
-                // esc
+        }
+        let mut txt = take(self.prompt_txt_mut());
+        self.line_editor
+            .edit(&mut txt, &mut self.input_cursor_pos, edit);
+        *self.prompt_txt_mut() = txt;
+        PromptKey::Edited
+    }
+
+    fn search(&mut self, canvas: &mut canvas::Canvas, key: Key) {
+        match self.read_prompt(canvas, key) {
+            PromptKey::Esc => {
                 self.mode = Mode::Normal;
-                self.search_history_index = self.search_history.len();
+                self.search_history.reset();
                 self.input_cursor_pos = 0;
                 canvas.reset_bottom_bar();
                 return;
-            } else if first_char == 127 {
-                // backspace
-                if self.input_cursor_pos >= 1 {
-                    self.search_txt.remove(self.input_cursor_pos - 1);
-                    // this is added so user knows what's being deleted.
-                    // there could be a problem when the lengths of the UTF-8 characters (the one
-                    // being deleted and the new one added on the left for alignment) are not equal,
-                    // making the cursor all over the place.
-                    if canvas.bottom_start > 0 {
-                        canvas.bottom_start -= 1;
-                    }
-                    self.input_cursor_pos -= 1;
-                }
-            } else if first_char == 10 {
-                // enter
+            }
+            PromptKey::Enter => {
                 self.save_history();
                 self.mode = Mode::Normal;
                 self.input_cursor_pos = 0;
                 canvas.reset_bottom_bar();
                 return;
-            } else {
-                // input characters
-                let mut search_txt_inserted = vec![];
-                let chars_len = chars.len();
-                search_txt_inserted.extend(&self.search_txt[0..self.input_cursor_pos]);
-                search_txt_inserted.append(&mut chars);
-                search_txt_inserted.extend(&self.search_txt[self.input_cursor_pos..]);
-                self.search_txt = search_txt_inserted;
-                self.input_cursor_pos += chars_len;
-            }
-        } else if op == Op::Up || op == Op::Down {
-            // search history
-            // ok to scroll: 1. at the end of history, search input is empty
-            //               2. currently in the process of scolling through history
-            //
-            // when user is not browsing history, search_history_index should be
-            // search_history.len()
-            if (self.search_history_index == self.search_history.len()
-                && self.search_txt.len() == 0)
-                || self.search_history_index < self.search_history.len()
-            {
-                match op {
-                    Op::Up => {
-                        if self.search_history_index > 0 {
-                            self.search_history_index -= 1;
-                        }
-                    }
-                    Op::Down => {
-                        if self.search_history_index < self.search_history.len() {
-                            self.search_history_index += 1;
-                        }
+            }
+            key @ (PromptKey::Up | PromptKey::Down) => {
+                // search history, only the searches starting with what's typed
+                let typed = self.search_txt.iter().collect::<String>();
+                let entry = match key {
+                    PromptKey::Up => self.search_history.up(&typed),
+                    _ => self.search_history.down(),
+                };
+                if let Some(entry) = entry {
+                    self.search_txt = entry.chars().collect();
+                    self.input_cursor_pos = self.search_txt.len();
+                }
+            }
+            PromptKey::Mode => self.search_mode = self.search_mode.next(),
+            PromptKey::Ignored => return,
+            PromptKey::Edited => self.search_history.reset(),
+        }
+        self.next_match(self.tab().cursor, false);
+    }
+
+    /// The Z prompt, visited directories are ranked as the user types
+    fn frecency_prompt(&mut self, canvas: &mut canvas::Canvas, key: Key) {
+        let height = self.height();
+        match self.read_prompt(canvas, key) {
+            key @ (PromptKey::Esc | PromptKey::Enter) => {
+                self.mode = Mode::Normal;
+                self.input_cursor_pos = 0;
+                canvas.reset_bottom_bar();
+                if matches!(key, PromptKey::Enter) {
+                    if let Some(path) = self.frecent.get(self.list.cursor).cloned() {
+                        self.jump(&path);
                     }
-                    _ => {}
                 }
-                if self.search_history_index < self.search_history.len() {
-                    self.search_txt = self.search_history[self.search_history_index].clone();
-                } else {
-                    self.search_txt = Vec::new();
+            }
+            PromptKey::Up => self.list.up(height),
+            PromptKey::Down => self.list.down(height),
+            PromptKey::Edited => self.update_frecent(),
+            PromptKey::Mode | PromptKey::Ignored => {}
+        }
+    }
+
+    /// Rank the visited directories with the text in the Z prompt
+    fn update_frecent(&mut self) {
+        self.frecent = self.frecency.query(&self.frecency_txt);
+        let items = self
+            .frecent
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        self.list = ListView::new("", items);
+    }
+
+    /// The filter prompt, content is narrowed down as the user types. Enter keeps the filter, Esc
+    /// clears it.
+    fn filter_prompt(&mut self, canvas: &mut canvas::Canvas, key: Key) {
+        match self.read_prompt(canvas, key) {
+            key @ (PromptKey::Esc | PromptKey::Enter) => {
+                self.mode = Mode::Normal;
+                self.input_cursor_pos = 0;
+                canvas.reset_bottom_bar();
+                if matches!(key, PromptKey::Esc) || self.tab().filter_txt.is_empty() {
+                    self.clear_filter();
                 }
-                self.input_cursor_pos = self.search_txt.len();
             }
-        } else if op == Op::Left || op == Op::Right {
-            // left and right arrow
-            match op {
-                Op::Left => {
-                    if self.input_cursor_pos > 0 {
-                        self.input_cursor_pos -= 1;
-                    }
+            PromptKey::Up => self.up(),
+            PromptKey::Down => self.down(),
+            PromptKey::Mode => {
+                if let Some(filter) = self.tab_mut().filter.as_mut() {
+                    filter.fuzzy = !filter.fuzzy;
                 }
-                Op::Right => {
-                    if self.input_cursor_pos + 1 <= self.search_txt.len() {
-                        self.input_cursor_pos += 1;
+                self.apply_filter();
+            }
+            PromptKey::Edited => self.apply_filter(),
+            PromptKey::Ignored => {}
+        }
+    }
+
+    /// Narrow content down to the entries matching the filter text, the best fuzzy match first
+    fn apply_filter(&mut self) {
+        let tab = self.tab_mut();
+        let filter = match tab.filter.as_mut() {
+            Some(filter) => filter,
+            None => return,
+        };
+        // an invalid regex filters nothing out
+        let names = filter.all.iter().map(Entry::name).collect::<Vec<&str>>();
+        let mut hits = matcher::score(&names, &tab.filter_txt, filter.fuzzy);
+        // the tree stays in order, with the directories the matches are in
+        if tab.tree {
+            hits.sort_by_key(|hit| hit.1);
+            hits = with_ancestors(&filter.all, hits);
+        }
+        tab.content = hits.iter().map(|hit| filter.all[hit.1].clone()).collect();
+        tab.search_matches = None;
+        filter.scores = hits.iter().map(|hit| hit.0).collect();
+        filter.positions = hits.into_iter().map(|hit| hit.2).collect();
+        self.top();
+    }
+
+    /// Add what's been read of a directory listed as it's read. With the filter on only the new
+    /// entries are ranked, and merged into the matches. The cursor stays where it is.
+    fn add_content(&mut self, more: Vec<Entry>) {
+        let tab = self.tab_mut();
+        tab.search_matches = None;
+        let filter = match tab.filter.as_mut() {
+            Some(filter) => filter,
+            None => {
+                tab.content.extend(more);
+                return;
+            }
+        };
+        let names = more.iter().map(Entry::name).collect::<Vec<&str>>();
+        let hits = matcher::score(&names, &tab.filter_txt, filter.fuzzy);
+        let scores = hits.iter().map(|hit| hit.0).collect::<Vec<i64>>();
+        let entries = hits.iter().map(|hit| more[hit.1].clone()).collect();
+        let order = matcher::merge_order(&filter.scores, &scores);
+        matcher::merge(&mut tab.content, entries, &order);
+        matcher::merge(
+            &mut filter.positions,
+            hits.into_iter().map(|hit| hit.2).collect(),
+            &order,
+        );
+        matcher::merge(&mut filter.scores, scores, &order);
+        filter.all.extend(more);
+    }
+
+    /// The find prompt, everything under current_path is ranked as the user types while a
+    /// background thread is still finding more
+    fn find_prompt(&mut self, canvas: &mut canvas::Canvas, key: Key) {
+        let height = self.height();
+        match self.read_prompt(canvas, key) {
+            key @ (PromptKey::Esc | PromptKey::Enter) => {
+                self.mode = Mode::Normal;
+                self.input_cursor_pos = 0;
+                canvas.reset_bottom_bar();
+                let finder = self.finder.take();
+                if let (PromptKey::Enter, Some(finder)) = (key, finder) {
+                    if let Some(found) = self.list.items.get(self.list.cursor) {
+                        let path = finder.root.join(found);
+                        self.reveal(&path);
                     }
                 }
-                _ => {}
             }
+            PromptKey::Up => self.list.up(height),
+            PromptKey::Down => self.list.down(height),
+            PromptKey::Mode => {
+                self.find_fuzzy = !self.find_fuzzy;
+                self.update_found();
+            }
+            PromptKey::Edited => self.update_found(),
+            PromptKey::Ignored => {}
         }
-        self.next_match(self.cursor, false);
     }
 
-    fn top(&mut self) {
-        self.cursor = 0;
-        self.window_start = 0;
-        self.refresh_preview = true;
+    /// Rank what's found with the text in the find prompt, the cursor stays where it was
+    fn update_found(&mut self) {
+        self.list.items.clear();
+        self.found_scores.clear();
+        self.found_ranked = 0;
+        self.rank_found();
     }
 
-    fn bottom(&mut self) {
-        if self.content.is_empty() == true {
+    /// Rank the paths found since the last time and merge them into the list, the cursor stays
+    /// where it was
+    fn rank_found(&mut self) {
+        let finder = match &self.finder {
+            Some(finder) => finder,
+            None => return,
+        };
+        let more = &finder.found[self.found_ranked..];
+        let hits = matcher::score(more, &self.find_txt, self.find_fuzzy);
+        let scores = hits.iter().map(|hit| hit.0).collect::<Vec<i64>>();
+        let items = hits.iter().map(|hit| more[hit.1].clone()).collect();
+        let order = matcher::merge_order(&self.found_scores, &scores);
+        matcher::merge(&mut self.list.items, items, &order);
+        matcher::merge(&mut self.found_scores, scores, &order);
+        self.found_ranked = finder.found.len();
+        self.list.cursor = self
+            .list
+            .cursor
+            .min(self.list.items.len().saturating_sub(1));
+        self.list.window_start = self.list.window_start.min(self.list.cursor);
+    }
+
+    /// Go to the directory of the path with the cursor on it, the past directory stacks are
+    /// rebuilt so h goes up from there
+    fn reveal(&mut self, path: &Path) {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
             return;
+        };
+        self.jump(dir);
+        if self.tab().current_path == dir {
+            self.select(&name.to_string_lossy());
         }
-        self.set_cursor_pos_centered(self.content.len() - 1);
-        self.refresh_preview = true;
     }
 
-    fn up(&mut self) {
-        if self.content.is_empty() == true {
+    /// The grep prompt, the files under current_path are searched for the regex after Enter
+    fn grep_prompt(&mut self, canvas: &mut canvas::Canvas, key: Key) {
+        let key = self.read_prompt(canvas, key);
+        if !matches!(key, PromptKey::Esc | PromptKey::Enter) {
             return;
         }
+        self.mode = Mode::Normal;
+        self.input_cursor_pos = 0;
+        canvas.reset_bottom_bar();
+        if matches!(key, PromptKey::Esc) || self.grep_txt.is_empty() {
+            return;
+        }
+        match matcher::search_regex(&self.grep_txt, SearchMode::Regex) {
+            Ok(re) => {
+                self.grepper = Some(Grepper::start(
+                    self.tab().current_path.clone(),
+                    re,
+                    self.waker(),
+                ));
+                self.list = ListView::default();
+                self.list.title = self.grep_status();
+                self.mode = Mode::GrepResults;
+            }
+            Err(e) => self.message = Some(format!("Invalid regex: {}", e)),
+        }
+    }
+
+    /// Keys in the grep results, the hits show up while the files are searched. Esc stops the
+    /// search, then closes the results.
+    fn grep_results(&mut self, key: Key) {
+        let height = self.height();
+        match key {
+            Key::Esc if self.grepper.as_ref().is_some_and(|g| g.running) => {
+                if let Some(grepper) = self.grepper.as_mut() {
+                    grepper.cancel();
+                }
+                self.update_hits();
+            }
+            Key::Esc | Key::Char('q') => {
+                self.grepper = None;
+                self.mode = Mode::Normal;
+            }
+            Key::Up | Key::Char('k') => self.list.up(height),
+            Key::Down | Key::Char('j') => self.list.down(height),
+            Key::End | Key::Char('G') => self.list.bottom(height),
+            Key::Enter | Key::Char('o') => self.open_hit(),
+            _ => {}
+        }
+    }
 
-        self.refresh_preview = true;
+    /// Add the new hits to the results
+    fn update_hits(&mut self) {
+        if let Some(grepper) = &self.grepper {
+            let new = grepper
+                .hits
+                .iter()
+                .skip(self.list.items.len())
+                .map(Hit::item);
+            self.list.items.extend(new.collect::<Vec<String>>());
+        }
+        self.list.title = self.grep_status();
+    }
 
-        self.cursor = if self.cursor as isize - 1 >= 0 {
-            self.cursor - 1
-        } else {
-            0
+    /// Progress of the grep for the bottom bar
+    fn grep_status(&self) -> String {
+        let grepper = match &self.grepper {
+            Some(grepper) => grepper,
+            None => return String::new(),
         };
+        format!(
+            "grep {}: {} hits in {} files{}  <enter> open  esc {}",
+            self.grep_txt.iter().collect::<String>(),
+            grepper.hits.len(),
+            grepper.files,
+            if grepper.running { ", searching.." } else { "" },
+            if grepper.running { "stop" } else { "close" },
+        )
+    }
 
-        if self.cursor < self.window_start {
-            self.window_start -= 1;
+    /// Open the file of the hit under the cursor at the line of the hit
+    fn open_hit(&self) {
+        let grepper = match &self.grepper {
+            Some(grepper) => grepper,
+            None => return,
+        };
+        if let Some(hit) = grepper.hits.get(self.list.cursor) {
+            self.open_file(
+                &self.opener_enter,
+                &grepper.root.join(&hit.path),
+                Some(hit.line),
+            );
         }
     }
 
+    /// matched/total shown on the right of the bottom bar while filtering or finding
+    pub fn counts(&self) -> Option<String> {
+        if let (Mode::Find, Some(finder)) = (self.mode, &self.finder) {
+            return Some(format!(
+                "{}{}/{}",
+                if finder.running { "finding.. " } else { "" },
+                self.list.items.len(),
+                finder.found.len()
+            ));
+        }
+        let filter = self
+            .tab()
+            .filter
+            .as_ref()
+            .map(|filter| format!("{}/{}", self.tab().content.len(), filter.all.len()));
+        match (self.search_counts(), filter) {
+            (Some(search), Some(filter)) => Some(format!("{} {}", search, filter)),
+            (search, filter) => search.or(filter),
+        }
+    }
+
+    /// [i/n], the cursor is on the i-th of n entries matching the search, '?' when it isn't on one
+    fn search_counts(&self) -> Option<String> {
+        if !matches!(self.mode, Mode::Normal | Mode::Search | Mode::RevSearch) {
+            return None;
+        }
+        if self.search_invalid() {
+            return Some(String::from("[invalid pattern]"));
+        }
+        self.search_re()?;
+        let found;
+        let matches = match &self.tab().search_matches {
+            Some(matches) => matches,
+            None => {
+                found = self.find_search_matches();
+                &found
+            }
+        };
+        let n = matches.len();
+        let i = matches
+            .binary_search(&self.tab().cursor)
+            .ok()
+            .map(|i| i + 1);
+        Some(match i {
+            Some(i) => format!("[{}/{}]", i, n),
+            None => format!("[?/{}]", n),
+        })
+    }
+
+    /// Show everything again, with the cursor still on the same entry
+    fn clear_filter(&mut self) {
+        if let Some(filter) = self.tab_mut().filter.take() {
+            let name = self.cursor_name();
+            self.tab_mut().content = filter.all;
+            self.tab_mut().search_matches = None;
+            self.top();
+            self.set_cursor_on(&name);
+        }
+        self.tab_mut().filter_txt = Vec::new();
+    }
+
+    fn top(&mut self) {
+        self.tab_mut().cursor = 0;
+        self.tab_mut().window_start = 0;
+        self.tab_mut().refresh_preview = true;
+    }
+
+    fn bottom(&mut self) {
+        if self.tab().content.is_empty() == true {
+            return;
+        }
+        self.set_cursor_pos_centered(self.tab().content.len() - 1);
+        self.tab_mut().refresh_preview = true;
+    }
+
+    fn up(&mut self) {
+        self.move_by(-1);
+    }
+
     fn down(&mut self) {
-        if self.content.is_empty() == true {
+        self.move_by(1);
+    }
+
+    /// Move the cursor by n entries, past either end it wraps around like n and N do
+    fn move_by(&mut self, n: isize) {
+        if self.tab().content.is_empty() {
             return;
         }
+        self.move_cursor(self.cursor_moved(n));
+    }
 
-        self.refresh_preview = true;
+    /// scrolloff, no more than half the window
+    fn scrolloff(&self) -> usize {
+        self.scrolloff.min(self.height().saturating_sub(1) / 2)
+    }
+
+    /// Put the cursor on the entry, the window scrolls as little as it can to show it with
+    /// scrolloff lines around it
+    fn move_cursor(&mut self, index: usize) {
+        let height = self.height();
+        let scrolloff = self.scrolloff();
+        self.tab_mut().cursor = index;
+        self.tab_mut().refresh_preview = true;
+        let top = index.saturating_sub(scrolloff);
+        let bottom = (index + scrolloff).min(self.tab().content.len().saturating_sub(1));
+        if top < self.tab().window_start {
+            self.tab_mut().window_start = top;
+        } else if bottom >= self.tab().window_start + height {
+            self.tab_mut().window_start = bottom + 1 - height;
+        }
+    }
 
-        let max_len = self.content.len();
-        self.cursor = if self.cursor + 1 < max_len {
-            self.cursor + 1
+    /// The first and the last entry the cursor can be on without scrolling, scrolloff lines in
+    /// from the edges of the window unless the window is at the start or the end of the content
+    fn cursor_bounds(&self) -> (usize, usize) {
+        let scrolloff = self.scrolloff();
+        let last = (self.tab().window_start + self.height())
+            .min(self.tab().content.len())
+            .saturating_sub(1);
+        let top = if self.tab().window_start == 0 {
+            0
+        } else {
+            (self.tab().window_start + scrolloff).min(last)
+        };
+        let bottom = if last + 1 >= self.tab().content.len() {
+            last
         } else {
-            max_len - 1
+            last.saturating_sub(scrolloff).max(top)
+        };
+        (top, bottom)
+    }
+
+    /// H, M and L, the cursor goes to the top, the middle or the bottom of the window. A count is
+    /// the line from the top for H and from the bottom for L.
+    fn screen_jump(&mut self, op: Op, count: usize) {
+        if self.tab().content.is_empty() {
+            return;
+        }
+        let (top, bottom) = self.cursor_bounds();
+        let index = match op {
+            Op::ScreenTop => (top + count - 1).min(bottom),
+            Op::ScreenBottom => bottom.saturating_sub(count - 1).max(top),
+            _ => {
+                let last =
+                    (self.tab().window_start + self.height()).min(self.tab().content.len()) - 1;
+                (self.tab().window_start + last) / 2
+            }
+        };
+        self.move_cursor(index);
+    }
+
+    /// zt, zz and zb, scroll so the cursor is at the top, the middle or the bottom of the window,
+    /// scrolloff lines in
+    fn scroll_cursor(&mut self, op: Op) {
+        if self.tab().content.is_empty() {
+            return;
+        }
+        let height = self.height();
+        let scrolloff = self.scrolloff();
+        self.tab_mut().window_start = match op {
+            Op::ScrollTop => self.tab().cursor.saturating_sub(scrolloff),
+            Op::ScrollBottom => {
+                let bottom = (self.tab().cursor + scrolloff).min(self.tab().content.len() - 1);
+                (bottom + 1).saturating_sub(height)
+            }
+            _ => self.tab().cursor.saturating_sub(height / 2),
         };
+    }
 
-        let display_height = get_height();
+    /// Ctrl + F and Ctrl + B, scroll by pages, the last two lines of a page are the first two of
+    /// the next one. When the window can't scroll any further, the cursor goes to the end.
+    fn page(&mut self, pages: isize) {
+        if self.tab().content.is_empty() {
+            return;
+        }
+        let window_start = self.tab().window_start;
+        self.scroll(pages * (self.height() as isize - 2).max(1));
+        if self.tab().window_start == window_start {
+            self.move_cursor(if pages > 0 {
+                self.tab().content.len() - 1
+            } else {
+                0
+            });
+        }
+    }
 
-        if (self.cursor) as isize > (self.window_start + display_height - 1) as isize {
-            self.window_start += 1;
+    /// f<char>, the cursor goes to the next entry starting with the character, ignoring case and
+    /// wrapping around. A count goes to the count-th one.
+    fn find_char(&mut self, c: char, count: usize) {
+        let len = self.tab().content.len();
+        let first = c.to_lowercase().collect::<String>();
+        let found = (1..=len)
+            .map(|i| (self.tab().cursor + i) % len)
+            .filter(|i| {
+                self.tab().content[*i]
+                    .name()
+                    .to_lowercase()
+                    .starts_with(&first)
+            })
+            .take(count)
+            .last();
+        match found {
+            Some(index) => self.move_cursor(index),
+            None => self.message = Some(format!("No entry starting with '{}'", c)),
         }
     }
 
     // don't need to refresh the preview window
     fn left(&mut self) {
-        let child = self.current_path.clone();
+        let child = self.tab().current_path.clone();
         // for example, root dir '/' doesn't have a file name
         if child.file_name() == None {
             return;
         }
 
-        self.preview = take(&mut self.content);
+        self.clear_filter();
+        self.jumplist.leave(&self.cursor_name());
+        // the directory is drawn as it's left in the tree view, without what's expanded
+        self.tab_mut().preview = take(&mut self.tab_mut().content)
+            .into_iter()
+            .filter(|entry| entry.depth == 0)
+            .map(|entry| entry.path.into())
+            .collect();
+        self.tab_mut().preview_truncated = false;
+        self.tab_mut().preview_dir = Some(child.clone());
 
         // access the parent dir and read its content
-        self.current_path = self
+        self.tab_mut().current_path = self
+            .tab_mut()
             .past_dir
             .pop()
             .expect("Failed to pop from past_dir in when exiting a directory");
-        self.read_content(&self.current_path.to_str().unwrap().to_string());
+        self.read_content(&self.tab().current_path.to_str().unwrap().to_string());
 
-        self.cursor = self
+        self.tab_mut().cursor = self
+            .tab_mut()
             .past_cursor
             .pop()
             .expect("Failed to pop from past_cursor");
 
-        self.window_start = self
+        self.tab_mut().window_start = self
+            .tab_mut()
             .past_window_start
             .pop()
             .expect("Failed to pop from past_window_start");
 
+        // a directory entered from further down the tree view is an entry of that tree
         let child_filename_str = child
-            .file_name()
-            .expect("Failed to get file name of current path to restore the directory")
+            .strip_prefix(&self.tab().current_path)
+            .expect("Failed to get the path of the directory left in its parent")
             .to_str()
-            .expect("Failed to do to_str()");
+            .expect("Failed to do to_str()")
+            .to_string();
 
         // 0 is set in init()
         let mut index: usize = 0;
 
         // find the child dir in parent directories
-        for (i, dir) in self.content.iter().enumerate() {
-            if dir.eq(child_filename_str) {
-                self.cursor = i;
+        for (i, dir) in self.tab().content.iter().enumerate() {
+            if *dir.path == child_filename_str {
+                self.tab_mut().cursor = i;
                 index = i;
                 break;
             }
         }
+        self.set_cursor_pos_centered(index);
+        // it may not have been read yet
+        self.select(&child_filename_str);
+        self.visited();
+    }
+
+    /// h with a count, up count directories or as far as it can go
+    fn leave_dirs(&mut self, count: usize) {
+        for _ in 0..count {
+            let dir = self.tab().current_path.clone();
+            self.left();
+            if self.tab().current_path == dir {
+                break;
+            }
+        }
+    }
+
+    /// l with a count, into the directory under the cursor, then the first one in it, count times
+    /// or until there isn't one
+    fn enter_dirs(&mut self, count: usize) {
+        for left in (0..count).rev() {
+            let dir = self.tab().current_path.clone();
+            self.right();
+            if self.tab().current_path == dir {
+                break;
+            }
+            // the next one is the first entry once it's sorted, which is once it's all read
+            if let Some(loader) = self.tab_mut().loader.as_mut() {
+                loader.enter = left;
+                break;
+            }
+        }
+    }
+
+    fn right(&mut self) {
+        if self.tab().content.len() <= 0 {
+            return;
+        }
+
+        self.tab_mut().refresh_preview = true;
+
+        let mut dir_under_cursor = self.tab().current_path.clone();
+        dir_under_cursor.push(&*self.tab().content[self.tab().cursor].path);
+        if dir_under_cursor.is_dir() == false {
+            return;
+        }
+
+        self.clear_filter();
+        self.jumplist.leave(&self.cursor_name());
+        let tab = self.tab_mut();
+        tab.past_dir.push(tab.current_path.clone());
+        tab.past_cursor.push(tab.cursor);
+        tab.past_window_start.push(tab.window_start);
+        tab.current_path = dir_under_cursor.clone();
+        // the preview is what's in it, unless it's not all there or it's still being read
+        if self.tab().tree
+            || self.tab().preview_truncated
+            || self.tab().preview_dir.as_ref() != Some(&dir_under_cursor)
+        {
+            self.read_content(&dir_under_cursor.to_string_lossy());
+        } else {
+            self.tab_mut().loader = None;
+            self.tab_mut().content = take(&mut self.tab_mut().preview)
+                .into_iter()
+                .map(Entry::from)
+                .collect();
+            self.tab_mut().search_matches = None;
+        }
+        self.top();
+        self.visited();
+    }
+
+    /// t, the tree view on or off. The cursor stays on its entry, or goes to the directory it's
+    /// in at the top of the tree.
+    fn toggle_tree(&mut self) {
+        let name = self.cursor_name();
+        self.tab_mut().tree = !self.tab().tree;
+        self.reload();
+        // off, an entry further down the tree isn't listed, the directory it's in at the top is
+        self.select(name.split('/').next().unwrap_or_default());
+    }
+
+    /// l in the tree view, expand the directory under the cursor in place. An expanded directory
+    /// is entered like outside of the tree view.
+    fn expand(&mut self) {
+        let Some(entry) = self.tab().content.get(self.tab().cursor) else {
+            return;
+        };
+        let path = self.tab().current_path.join(&*entry.path);
+        if !path.is_dir() {
+            return;
+        }
+        if self.tab_mut().expanded.insert(path) {
+            self.reload();
+        } else {
+            self.right();
+        }
+    }
+
+    /// h in the tree view, collapse the directory under the cursor, or the one the entry under
+    /// the cursor is in. At the top of the tree it goes to the parent directory.
+    fn collapse(&mut self) {
+        let Some(entry) = self.tab().content.get(self.tab().cursor).cloned() else {
+            self.left();
+            return;
+        };
+        let tab = self.tab_mut();
+        if tab.expanded.remove(&tab.current_path.join(&*entry.path)) {
+            self.reload();
+            return;
+        }
+        match entry.parent() {
+            Some(parent) => {
+                let tab = self.tab_mut();
+                tab.expanded.remove(&tab.current_path.join(parent));
+                self.reload();
+                self.select(parent);
+            }
+            None => self.left(),
+        }
+    }
+
+    /// Read the file and directory names in the current directory, and in its expanded
+    /// directories in the tree view. A huge directory is listed as it's read.
+    fn read_content(&mut self, path: &str) {
+        self.tab_mut().content = Vec::new();
+        self.tab_mut().search_matches = None;
+        self.load(PathBuf::from(path), true);
+    }
+
+    /// The expanded directories if it's the tree view
+    fn expanded(&self) -> Option<&HashSet<PathBuf>> {
+        self.tab().tree.then_some(&self.tab().expanded)
+    }
+
+    /// The tab being shown
+    pub fn tab(&self) -> &Tab {
+        &self.tabs[self.tab_index]
+    }
+
+    pub fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.tab_index]
+    }
+
+    /// Rows for the entries, the bottom line and the tab bar take one each
+    fn height(&self) -> usize {
+        let height = get_height();
+        if has_tab_bar(self.tabs.len(), height + 1) {
+            height - 1
+        } else {
+            height
+        }
+    }
+
+    /// The labels of the tab bar, the directory name of each tab. There's no tab bar with only
+    /// one tab.
+    pub fn tab_names(&self) -> Vec<String> {
+        if self.tabs.len() < 2 {
+            return Vec::new();
+        }
+        self.tabs
+            .iter()
+            .map(|tab| match tab.current_path.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => String::from("/"),
+            })
+            .collect()
+    }
+
+    /// Open a tab at the index, showing the directory with the cursor on the entry
+    fn open_tab(&mut self, index: usize, path: &Path, cursor: &str) {
+        let tab = Tab {
+            refresh_preview: true,
+            ..Tab::default()
+        };
+        self.tabs.insert(index, tab);
+        self.tab_index = index;
+        if let Some(other) = self.pane.as_mut().filter(|other| **other >= index) {
+            *other += 1;
+        }
+        self.init(&path.to_string_lossy());
+        self.select(cursor);
+    }
+
+    /// gn, a new tab next to the current one, in the same directory
+    fn new_tab(&mut self) {
+        let path = self.tab().current_path.clone();
+        let cursor = self.cursor_name();
+        self.open_tab(self.tab_index + 1, &path, &cursor);
+    }
+
+    /// gc, close the current tab, the one after it is shown, or the one before it if it was the
+    /// last one. Closing a pane of the dual-pane mode shows the other one alone.
+    fn close_tab(&mut self) {
+        if self.tabs.len() == 1 {
+            self.message = Some(String::from("Can't close the last tab"));
+            return;
+        }
+        let closed = self.tab_index;
+        self.tabs.remove(closed);
+        self.tab_index = match self.pane.take() {
+            Some(other) if other > closed => other - 1,
+            Some(other) => other,
+            None => closed.min(self.tabs.len() - 1),
+        };
+        self.reload();
+    }
+
+    /// Show another tab, its directory is read again since it may have changed meanwhile. In the
+    /// dual-pane mode the other pane stays, unless it's the one shown, then the two swap.
+    fn switch_tab(&mut self, index: usize) {
+        if index == self.tab_index || index >= self.tabs.len() {
+            return;
+        }
+        if self.pane == Some(index) {
+            self.pane = Some(self.tab_index);
+        }
+        self.tab_index = index;
+        self.reload();
+    }
+
+    /// Read the current directory again in the background, the listing stays as it is until it's
+    /// read. Then the filter is applied again and the cursor stays on the same entry if it's still
+    /// there.
+    fn reload(&mut self) {
+        let dir = self.tab().current_path.clone();
+        // the cursor still goes where it was meant to once it's read
+        let select = self
+            .tab_mut()
+            .loader
+            .take()
+            .and_then(|loader| loader.select);
+        self.load(dir, false);
+        if let Some(loader) = self.tab_mut().loader.as_mut() {
+            loader.select = select;
+        }
+    }
+
+    /// Read a directory for the tab's listing on another thread. Shown, it's listed as it's read
+    /// and sorted once it's all read, what's read within LOAD_WAIT is there right away so a small
+    /// one doesn't flicker. Otherwise the listing stays as it is until it's all read.
+    fn load(&mut self, dir: PathBuf, shown: bool) {
+        let expanded = self.expanded().cloned();
+        let mut loader = Loader::start(dir, expanded, shown, self.waker());
+        if shown {
+            loader.poll(LOAD_WAIT);
+        }
+        self.tab_mut().loader = Some(loader);
+        self.loaded();
+    }
+
+    /// Take in what's been read of the tab's directory
+    fn loaded(&mut self) {
+        let Some(mut loader) = self.tab_mut().loader.take() else {
+            return;
+        };
+        // only a shown one is sent the batches
+        if !loader.entries.is_empty() {
+            self.add_content(take(&mut loader.entries));
+        }
+        if !loader.read {
+            self.tab_mut().loader = Some(loader);
+            return;
+        }
+        // the cursor stays on its entry, or goes where it was meant to be if it hasn't moved
+        let moved = loader
+            .select
+            .as_ref()
+            .is_some_and(|(_, cursor)| *cursor != self.tab().cursor);
+        // l with a count goes on from the first entry, unless the cursor's been moved off it
+        let enter = if self.tab().cursor == 0 {
+            loader.enter
+        } else {
+            0
+        };
+        match loader.all.take() {
+            Some(content) => self.apply_content(content),
+            None => self.merge_batches(),
+        }
+        match &loader.select {
+            Some((name, _)) if !moved => self.set_cursor_on(name),
+            _ => {}
+        }
+        if enter > 0 {
+            self.top();
+            self.enter_dirs(enter);
+        }
+    }
+
+    /// A directory listed as it's read comes in sorted batches, they're merged into one once it's
+    /// all read. The cursor stays on its entry.
+    fn merge_batches(&mut self) {
+        let name = self.cursor_name();
+        let tab = self.tab_mut();
+        let listed = match tab.filter.as_mut() {
+            Some(filter) => &mut filter.all,
+            None => &mut tab.content,
+        };
+        // the sort is stable, it merges what's already sorted
+        listed.sort_by(|a, b| by_name(&a.path, &b.path));
+        tab.search_matches = None;
+        if self.cursor_name() != name {
+            self.set_cursor_on(&name);
+        }
+    }
+
+    /// Wait for the current directory to be all read
+    #[cfg(test)]
+    pub fn wait_loaded(&mut self) {
+        while let Some(loader) = self.tab_mut().loader.as_mut() {
+            loader.poll(LOAD_WAIT);
+            self.loaded();
+        }
+    }
+
+    /// How many entries of the current directory have been read while it's listed as it's read
+    pub fn loading(&self) -> Option<usize> {
+        self.tab()
+            .loader
+            .as_ref()
+            .filter(|loader| loader.shown)
+            .map(|loader| loader.count)
+    }
+
+    /// Show the content read again for the current directory
+    fn apply_content(&mut self, content: Vec<Entry>) {
+        let name = self.cursor_name();
+        match self.tab_mut().filter.as_mut() {
+            Some(filter) => {
+                filter.all = content;
+                self.apply_filter();
+            }
+            None => {
+                self.tab_mut().content = content;
+                self.tab_mut().search_matches = None;
+            }
+        }
+        self.tab_mut().marked.retain(|path| path.exists());
+        self.tab_mut().cursor = self
+            .tab()
+            .cursor
+            .min(self.tab().content.len().saturating_sub(1));
+        self.tab_mut().window_start = self.tab().window_start.min(self.tab().cursor);
+        if self.cursor_name() != name {
+            self.set_cursor_on(&name);
+        }
+        self.tab_mut().refresh_preview = true;
+        self.tab_mut().parent_of = None;
+    }
+
+    /// R, read the listings on the screen again, both panes in the dual-pane mode
+    fn reload_panes(&mut self) {
+        if self.other_pane().is_some() {
+            self.in_other_pane(Browser::reload);
+        }
+        self.reload();
+    }
+
+    /// Do something in the other pane of the dual-pane mode without switching to it
+    fn in_other_pane(&mut self, f: impl FnOnce(&mut Browser)) {
+        if let Some(other) = self.pane {
+            self.in_tab(other, f);
+        }
+    }
+
+    /// Do something in another tab without switching to it
+    fn in_tab(&mut self, index: usize, f: impl FnOnce(&mut Browser)) {
+        let shown = replace(&mut self.tab_index, index);
+        f(self);
+        self.tab_index = shown;
+    }
+
+    /// The directories on the screen: the current one, the expanded ones in the tree view, the
+    /// one in the preview and the other pane's
+    fn shown_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.tab().current_path.clone()];
+        let mut add = |dir: PathBuf| {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        };
+        if self.tab().tree {
+            for entry in self.tab().content.iter() {
+                if let Some(parent) = entry.parent() {
+                    add(self.tab().current_path.join(parent));
+                }
+            }
+        }
+        if let Some(entry) = self.tab().content.get(self.tab().cursor) {
+            let preview = self.tab().current_path.join(&*entry.path);
+            if preview.is_dir() {
+                add(preview);
+            }
+        }
+        if let Some(other) = self.other_pane() {
+            add(other.current_path.clone());
+        }
+        dirs
+    }
+
+    /// What wakes the event loop up when a job is done, None outside of it
+    fn waker(&self) -> Option<Waker> {
+        self.events.as_ref().map(Events::waker)
+    }
+
+    /// Wait for a key, meanwhile the directories on the screen are watched, the jobs deliver what
+    /// they've done and the terminal may be resized. When a change settles, a job is done or the
+    /// terminal is resized, false is returned for the screen to be drawn again.
+    fn wait_event(&mut self) -> bool {
+        if self.keys.ready(0) {
+            return true;
+        }
+        let dirs = self.shown_dirs();
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.watch(&dirs);
+        }
+        let watch = self.watcher.as_ref().map(Watcher::fd);
+        loop {
+            // until the changes settle
+            let timeout = match self.changes {
+                Some((first, last)) => {
+                    let left = SETTLE
+                        .saturating_sub(last.elapsed())
+                        .min(MAX_SETTLE.saturating_sub(first.elapsed()));
+                    left.as_millis() as i32 + 1
+                }
+                None => -1,
+            };
+            let Some(events) = self.events.as_mut() else {
+                return true;
+            };
+            let ready = events.wait(watch, timeout);
+            if ready.watch && self.watcher.as_mut().is_some_and(Watcher::changed) {
+                let now = Instant::now();
+                self.changes = Some((self.changes.map_or(now, |(first, _)| first), now));
+            }
+            if self.changes.is_some_and(|(first, last)| {
+                last.elapsed() >= SETTLE || first.elapsed() >= MAX_SETTLE
+            }) {
+                self.changes = None;
+                self.reload_panes();
+                return false;
+            }
+            if ready.resize || (ready.work && self.poll_jobs(Duration::ZERO)) {
+                return false;
+            }
+            if ready.key {
+                return true;
+            }
+        }
+    }
+
+    /// Take what the jobs that are done have done, waiting at most timeout for each
+    ///
+    /// returns
+    ///  whether anything on the screen changed
+    fn poll_jobs(&mut self, timeout: Duration) -> bool {
+        let mut done = false;
+        // first, since the listings are read again after it
+        if let Some(pasted) = self
+            .paste_job
+            .as_ref()
+            .and_then(|(_, job)| job.wait(timeout))
+        {
+            if let Some((dir, _)) = self.paste_job.take() {
+                self.pasted(&dir, pasted);
+            }
+            done = true;
+        }
+        // every tab's, a tab that's gone somewhere else has dropped the loader of where it was
+        for i in 0..self.tabs.len() {
+            if self.tabs[i]
+                .loader
+                .as_mut()
+                .is_some_and(|loader| loader.poll(timeout))
+            {
+                self.in_tab(i, Browser::loaded);
+                done = true;
+            }
+        }
+        if let Some(preview) = self
+            .previewer
+            .as_mut()
+            .and_then(|previewer| previewer.answer(timeout))
+        {
+            self.previewed(preview);
+            done = true;
+        }
+        // what's been found so far, they aren't waited for
+        if self.finder.as_mut().is_some_and(Finder::poll) {
+            self.rank_found();
+            done = true;
+        }
+        if self.grepper.as_mut().is_some_and(Grepper::poll) {
+            self.update_hits();
+            done = true;
+        }
+        done
+    }
+
+    /// Open the tabs of the last session around the current one, which is where it ended
+    pub fn restore_tabs(&mut self, tabs: Vec<Jump>, current: usize) {
+        let mut shown = self.tab_index;
+        for (i, jump) in tabs.iter().enumerate() {
+            if i == current || !jump.path.is_dir() {
+                continue;
+            }
+            if i < current {
+                self.open_tab(shown, &jump.path, &jump.cursor);
+                shown += 1;
+            } else {
+                self.open_tab(self.tabs.len(), &jump.path, &jump.cursor);
+            }
+        }
+        self.switch_tab(shown);
+    }
+
+    /// Where each tab is, the current one included
+    fn tab_jumps(&self) -> Vec<Jump> {
+        (0..self.tabs.len())
+            .map(|i| {
+                if i == self.tab_index {
+                    self.tab().jump()
+                } else {
+                    self.tabs[i].jump()
+                }
+            })
+            .collect()
+    }
+
+    /// yy and dd, the marked entries or the one under the cursor are pasted by pp later, in any
+    /// tab. A cut moves them instead of copying them.
+    fn copy(&mut self, cut: bool) {
+        let paths = self.selection();
+        if paths.is_empty() {
+            return;
+        }
+        self.message = Some(format!(
+            "{} {} {}, pp to paste",
+            if cut { "Cut" } else { "Copied" },
+            paths.len(),
+            if paths.len() == 1 { "entry" } else { "entries" }
+        ));
+        self.copied = paths;
+        self.cut = cut;
+    }
+
+    /// The marked entries, or the one under the cursor if none is marked. The marks are cleared.
+    fn selection(&mut self) -> Vec<PathBuf> {
+        if !self.tab().marked.is_empty() {
+            take(&mut self.tab_mut().marked)
+        } else if self.tab().content.is_empty() {
+            Vec::new()
+        } else {
+            vec![self.tab().current_path.join(self.cursor_name())]
+        }
+    }
 
-        self.set_cursor_pos_centered(index);
+    /// Whether a paste is still in progress, there's only one at a time
+    fn pasting(&mut self) -> bool {
+        if self.paste_job.is_some() {
+            self.message = Some(String::from("Still pasting, esc cancels"));
+        }
+        self.paste_job.is_some()
     }
 
-    fn right(&mut self) {
-        if self.content.len() <= 0 {
+    /// pp, paste what was copied or cut into the current directory
+    fn paste(&mut self) {
+        if self.pasting() {
             return;
         }
+        if self.copied.is_empty() {
+            self.message = Some(String::from("Nothing to paste, yy copies and dd cuts"));
+            return;
+        }
+        // a cut stays in copied until it's moved, what isn't can be pasted again
+        let paths = self.copied.clone();
+        let dir = self.tab().current_path.clone();
+        self.paste_into(paths, dir, self.cut);
+    }
 
-        self.refresh_preview = true;
-
-        let mut dir_under_cursor = self.current_path.clone();
-        dir_under_cursor.push(&self.content[self.cursor]);
-        if dir_under_cursor.is_dir() == false {
+    /// F5 and F6 in the dual-pane mode, copy or move the marked entries, or the one under the
+    /// cursor, into the directory of the other pane
+    fn paste_to_other(&mut self, cut: bool) {
+        let Some(other) = self.other_pane() else {
+            self.message = Some(String::from("The other pane is shown by Ctrl + W"));
+            return;
+        };
+        let dir = other.current_path.clone();
+        if self.pasting() {
             return;
         }
+        let paths = self.selection();
+        self.paste_into(paths, dir, cut);
+    }
 
-        self.past_dir.push(self.current_path.clone());
-        self.past_cursor.push(self.cursor);
-        self.past_window_start.push(self.window_start);
-        self.current_path = dir_under_cursor.clone();
-        self.content = take(&mut self.preview);
-        self.top();
+    /// Copy or move the paths into the directory on another thread
+    fn paste_into(&mut self, paths: Vec<PathBuf>, dir: PathBuf, cut: bool) {
+        if paths.is_empty() {
+            return;
+        }
+        let into = dir.clone();
+        let job = Job::spawn(self.waker(), move |cancel| {
+            paste_paths(&paths, &into, cut, cancel)
+        });
+        self.paste_job = Some((dir, job));
     }
 
-    /// Read the file and directory names in the current directory
-    fn read_content(&mut self, path: &str) {
-        self.content = match read_dir(path) {
-            Ok(entries) => entries
-                .map(|_e| match _e {
-                    Ok(e) => match e.file_name().into_string() {
-                        Ok(filename) => filename,
-                        Err(filename_os) => filename_os.to_string_lossy().to_string(),
-                    },
-                    Err(_) => String::new(),
-                })
-                .collect::<Vec<String>>(),
-            Err(_) => Vec::new(),
+    /// A paste is done, the listings are read again with the cursor on what's pasted. What's
+    /// been moved of a cut isn't pasted again.
+    fn pasted(&mut self, dir: &Path, pasted: Pasted) {
+        if self.cut {
+            self.copied.retain(|path| path.symlink_metadata().is_ok());
+        }
+        self.message = match pasted.errors.len() {
+            0 if pasted.cancelled => Some(String::from("Paste cancelled")),
+            0 => None,
+            1 => pasted.errors.first().cloned(),
+            n => Some(format!("{} and {} more failed", pasted.errors[0], n - 1)),
+        };
+        let show = |b: &mut Browser| {
+            b.reload();
+            match &pasted.first {
+                Some(name) if b.tab().current_path == dir => b.select(name),
+                _ => {}
+            }
         };
+        if self.other_pane().is_some() {
+            self.in_other_pane(show);
+        }
+        show(self);
+    }
+
+    /// Esc while pasting, it stops after what's pasted so far. A cut that isn't all moved can be
+    /// pasted again.
+    fn cancel_paste(&mut self) {
+        if let Some((_, job)) = self.paste_job.as_ref() {
+            job.cancel();
+        }
+        self.message = Some(String::from("Cancelling the paste…"));
+    }
+
+    /// Ctrl + W, show the current tab and the next one side by side or go back to one. A tab is
+    /// opened in the same directory if there's no other one.
+    fn toggle_dual(&mut self) {
+        if self.pane.take().is_some() {
+            return;
+        }
+        let index = self.tab_index;
+        if self.tabs.len() == 1 {
+            let path = self.tab().current_path.clone();
+            let cursor = self.cursor_name();
+            self.open_tab(1, &path, &cursor);
+            self.tab_index = index;
+        }
+        self.pane = Some((index + 1) % self.tabs.len());
+    }
 
-        self.content
-            .sort_by(|d1, d2| d1.to_lowercase().cmp(&d2.to_lowercase()));
+    /// The pane without the focus in the dual-pane mode
+    pub fn other_pane(&self) -> Option<&Tab> {
+        self.pane.map(|other| &self.tabs[other])
     }
 
     /// quit trans and goto the directory in the left window
     pub fn exit_cur_dir(&self) {
+        self.finish(Exit::Cd, &self.tab().current_path);
+    }
+
+    /// Write the destination path in --dest-format and exit with the exit code of the reason
+    fn finish(&self, reason: Exit, path: &PathBuf) -> ! {
+        // a paste isn't left half done
+        if let Some((_, job)) = self.paste_job.as_ref() {
+            job.wait(Duration::MAX);
+        }
         util::exit_albuf();
-        util::print_path(&self.current_path, (&self.dest_file).as_ref());
-        exit(0);
+        let _ = self.jumplist.save(&self.cursor_name());
+        let _ = tab::save_tabs(
+            util::state_file(consts::TABS_FILE),
+            &self.tab_jumps(),
+            self.tab_index,
+        );
+        // the current directory is already counted when it's visited
+        let _ = self.frecency.save(match reason {
+            Exit::Cd if path != &self.tab().current_path => Some(path),
+            _ => None,
+        });
+        // the choice went to the chooser's file, which may be stdout, there's nothing else to print
+        // unless --dest asks for it
+        if matches!(reason, Exit::Choose) && self.dest_file.is_none() {
+            exit(reason.code());
+        }
+        match self.dest_format {
+            DestFormat::Lines => util::print_path(path, self.dest_file.as_ref()),
+            DestFormat::Nul => {
+                let mut content = path.as_os_str().as_bytes().to_vec();
+                content.push(0);
+                util::print_dest(&content, self.dest_file.as_ref());
+            }
+            DestFormat::Json => util::print_dest(
+                self.dest_json(reason, path).as_bytes(),
+                self.dest_file.as_ref(),
+            ),
+        }
+        exit(reason.code());
+    }
+
+    /// Exit reason, destination, and what the cursor was on for scripts and editor plugins
+    fn dest_json(&self, reason: Exit, path: &PathBuf) -> String {
+        let json_path = |p: &PathBuf| util::json_str(&p.to_string_lossy());
+        let cursor = if self.tab().cursor < self.tab().content.len() {
+            let mut cursor = self.tab().current_path.clone();
+            cursor.push(&*self.tab().content[self.tab().cursor].path);
+            json_path(&cursor)
+        } else {
+            String::from("null")
+        };
+        format!(
+            "{{\"reason\":{},\"path\":{},\"current_path\":{},\"cursor\":{},\"marked\":[{}]}}\n",
+            util::json_str(reason.reason()),
+            json_path(path),
+            json_path(&self.tab().current_path),
+            cursor,
+            self.tab()
+                .marked
+                .iter()
+                .map(json_path)
+                .collect::<Vec<String>>()
+                .join(",")
+        )
     }
 
     /// quit trans and goto the directory under the cursor
     ///  or
     /// open the file under the cursor with opener command
     fn exit_under_cursor(&self, op: Op) {
-        let mut dir = self.current_path.clone();
-        dir.push(&self.content[self.cursor]);
+        let mut dir = self.tab().current_path.clone();
+        dir.push(&*self.tab().content[self.tab().cursor].path);
         let opener = match op {
             Op::ExitCursorO => &self.opener_o,
             Op::ExitCursorEnter => &self.opener_enter,
@@ -636,30 +2241,35 @@ impl Browser {
         };
 
         if dir.is_dir() == false {
-            // reduce color flickering (the flickering color is the bottom bar color)
-            util::reduce_flicker();
-
-            if let Ok(_) = Command::new(&(*opener).comm)
-                .args(&(*opener).args)
-                .arg(dir.to_str().unwrap())
-                .status()
-            {
-                // empty, successfully opened with opener
-            } else {
-                Command::new(consts::OPENER)
-                    .arg(dir.to_str().unwrap())
-                    .status()
-                    .expect(&format!(
-                        "Failed to open {} with default opener {}",
-                        dir.to_str().unwrap(),
-                        consts::OPENER
-                    ));
-            }
+            self.open_file(opener, &dir, None);
         } else {
-            util::exit_albuf();
-            util::print_path(&dir, (&self.dest_file).as_ref());
-            exit(0);
+            self.finish(Exit::Cd, &dir);
         };
+    }
+
+    /// Open the file with the opener, at the line if there's one
+    fn open_file(&self, opener: &Opener, file: &Path, line: Option<usize>) {
+        let args = opener.args_at(line);
+        // GUI programs don't need the terminal, leave the screen as it is
+        if opener.detach && util::spawn_detached(&opener.comm, &args, file).is_ok() {
+            return;
+        }
+
+        // reduce color flickering (the flickering color is the bottom bar color)
+        util::reduce_flicker();
+
+        if let Ok(_) = Command::new(&opener.comm).args(&args).arg(file).status() {
+            // empty, successfully opened with opener
+        } else {
+            Command::new(consts::OPENER)
+                .arg(file)
+                .status()
+                .expect(&format!(
+                    "Failed to open {} with default opener {}",
+                    file.to_string_lossy(),
+                    consts::OPENER
+                ));
+        }
 
         // when an opener exits, it also exits the alternate buffer, and enables cursor, need to
         // stay in albuf and hide cursor in trans
@@ -667,45 +2277,195 @@ impl Browser {
         util::hide_cursor();
     }
 
+    /// Mark or unmark the entry under the cursor, then move down
+    fn toggle_mark(&mut self) {
+        if self.tab().content.is_empty() {
+            return;
+        }
+        let mut path = self.tab().current_path.clone();
+        path.push(&*self.tab().content[self.tab().cursor].path);
+        match self.tab().marked.iter().position(|m| *m == path) {
+            Some(i) => {
+                self.tab_mut().marked.remove(i);
+            }
+            None => self.tab_mut().marked.push(path),
+        }
+        self.down();
+    }
+
+    /// Chooser mode: pick the marked entries, or the one under the cursor. When picking files,
+    /// a directory under the cursor is entered instead.
+    fn choose_under_cursor(&mut self) {
+        let dirs = match &self.chooser {
+            Some(chooser) => chooser.dirs,
+            None => return,
+        };
+
+        // entries that can't be chosen are ignored
+        let marked = self
+            .tab()
+            .marked
+            .iter()
+            .filter(|m| m.is_dir() == dirs)
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        if !marked.is_empty() {
+            self.choose(marked);
+        }
+
+        if self.tab().content.is_empty() {
+            return;
+        }
+        let mut path = self.tab().current_path.clone();
+        path.push(&*self.tab().content[self.tab().cursor].path);
+        if path.is_dir() == dirs {
+            self.choose(vec![path]);
+        } else if !dirs {
+            self.right();
+        }
+    }
+
+    /// Output the chosen paths and quit trans
+    fn choose(&self, paths: Vec<PathBuf>) {
+        if let Some(chooser) = &self.chooser {
+            let _ = util::write_paths(&mut (&chooser.out), &paths, chooser.nul);
+        }
+        self.finish(Exit::Choose, &self.tab().current_path);
+    }
+
+    /// Enter chooser mode, the chosen paths will be written to out
+    pub fn set_chooser(&mut self, out: File, dirs: bool, nul: bool) {
+        self.chooser = Some(Chooser { out, dirs, nul });
+    }
+
     fn quit(&self) {
-        util::exit_albuf();
-        util::print_path(&self.original_path, (&self.dest_file).as_ref());
-        exit(0);
+        self.finish(Exit::Quit, &self.original_path);
+    }
+
+    /// How the destination is written, see --dest-format
+    pub fn set_dest_format(&mut self, dest_format: DestFormat) {
+        self.dest_format = dest_format;
     }
 
     fn pageup(&mut self) {
-        if self.content.is_empty() == true {
+        if self.tab().content.is_empty() == true {
             return;
         }
 
-        self.refresh_preview = true;
+        self.tab_mut().refresh_preview = true;
 
-        let height = get_height();
+        let height = self.height();
         let half_page = height / 2;
 
-        let pos = if self.cursor < half_page {
+        let pos = if self.tab().cursor < half_page {
             0
         } else {
-            self.cursor - half_page
+            self.tab().cursor - half_page
         };
 
         self.set_cursor_pos_centered(pos);
     }
 
+    /// Scroll the window by lines, the cursor is kept in the window, scrolloff lines in
+    fn scroll(&mut self, lines: isize) {
+        if self.tab().content.is_empty() {
+            return;
+        }
+        let height = self.height();
+        // the window may already start past the last full page, e.g. after G
+        let max_start = self
+            .tab()
+            .content
+            .len()
+            .saturating_sub(height)
+            .max(self.tab().window_start);
+        self.tab_mut().window_start = self
+            .tab()
+            .window_start
+            .saturating_add_signed(lines)
+            .min(max_start);
+        let (top, bottom) = self.cursor_bounds();
+        let cursor = self.tab().cursor.clamp(top, bottom);
+        if cursor != self.tab().cursor {
+            self.tab_mut().cursor = cursor;
+            self.tab_mut().refresh_preview = true;
+        }
+    }
+
+    /// A click moves the cursor to where it is, a click in the preview column goes into the
+    /// directory first, and one in the parent column goes out of it
+    ///
+    /// returns
+    ///  what else the mouse does, e.g. a double click opens like Enter
+    fn mouse(&mut self, mouse: Mouse, canvas: &canvas::Canvas) -> Op {
+        match (mouse.kind, canvas.hit(mouse.row, mouse.col)) {
+            (MouseKind::WheelUp, _) => self.scroll(-WHEEL_LINES),
+            (MouseKind::WheelDown, _) => self.scroll(WHEEL_LINES),
+            (MouseKind::Click, canvas::Hit::BottomBar) => return Op::Search,
+            (MouseKind::Click | MouseKind::DoubleClick, canvas::Hit::Current(row))
+                if self.tab().window_start + row < self.tab().content.len() =>
+            {
+                self.tab_mut().cursor = self.tab().window_start + row;
+                self.tab_mut().refresh_preview = true;
+                if mouse.kind == MouseKind::DoubleClick {
+                    return if self
+                        .tab()
+                        .current_path
+                        .join(&*self.tab().content[self.tab().cursor].path)
+                        .is_dir()
+                    {
+                        Op::Right
+                    } else {
+                        Op::ExitCursorEnter
+                    };
+                }
+            }
+            (MouseKind::Click, canvas::Hit::Parent(row))
+                if self.parent_window_start() + row < self.tab().parent.len() =>
+            {
+                let name = self.tab().parent[self.parent_window_start() + row].clone();
+                self.left();
+                self.select(&name);
+            }
+            (MouseKind::Click, canvas::Hit::Preview(row)) if row < self.tab().preview.len() => {
+                let name = self.tab().preview[row].clone();
+                let dir = self.tab().current_path.clone();
+                self.right();
+                // a file, or the tree view expanding the directory in place, stays where it is
+                if self.tab().current_path != dir {
+                    self.select(&name);
+                }
+            }
+            // the other pane gets the focus
+            (MouseKind::Click, canvas::Hit::Other(row)) => {
+                let Some(other) = self.pane else {
+                    return Op::Noop;
+                };
+                self.switch_tab(other);
+                if self.tab().window_start + row < self.tab().content.len() {
+                    self.tab_mut().cursor = self.tab().window_start + row;
+                    self.tab_mut().refresh_preview = true;
+                }
+            }
+            _ => {}
+        }
+        Op::Noop
+    }
+
     fn pagedown(&mut self) {
-        if self.content.is_empty() == true {
+        if self.tab().content.is_empty() == true {
             return;
         }
 
-        self.refresh_preview = true;
+        self.tab_mut().refresh_preview = true;
 
-        let height = get_height();
+        let height = self.height();
         let half_page = height / 2;
 
-        let pos = if self.cursor + half_page >= self.content.len() {
-            self.content.len() - 1
+        let pos = if self.tab().cursor + half_page >= self.tab().content.len() {
+            self.tab().content.len() - 1
         } else {
-            self.cursor + half_page
+            self.tab().cursor + half_page
         };
 
         self.set_cursor_pos_centered(pos);
@@ -717,12 +2477,44 @@ impl Opener {
         let mut opener = Opener {
             comm: comm,
             args: vec![],
+            detach: false,
         };
         if args.is_some() {
             opener.args = args.unwrap()
         }
+        // a trailing '&' works like it does in the shell
+        if opener.args.last().is_some_and(|arg| arg == "&") {
+            opener.args.pop();
+            opener.detach = true;
+        }
         opener
     }
+
+    /// Arguments to open a file at the line. '{line}' in the arguments is replaced with the line
+    /// number, editors that take '+<line>' get it without one.
+    fn args_at(&self, line: Option<usize>) -> Vec<OsString> {
+        let line = line.unwrap_or(1).to_string();
+        if self
+            .args
+            .iter()
+            .any(|arg| arg.to_string_lossy().contains(consts::LINE_PLACEHOLDER))
+        {
+            return self
+                .args
+                .iter()
+                .map(|arg| match arg.to_str() {
+                    Some(arg) => OsString::from(arg.replace(consts::LINE_PLACEHOLDER, &line)),
+                    None => arg.clone(),
+                })
+                .collect();
+        }
+        let mut args = self.args.clone();
+        let name = Path::new(&self.comm).file_name().unwrap_or_default();
+        if line != "1" && LINE_EDITORS.iter().any(|editor| name == *editor) {
+            args.push(OsString::from(format!("+{}", line)));
+        }
+        args
+    }
 }
 
 pub fn new(path: &str, dest_file: Option<String>, config_path: Option<&str>) -> Browser {
@@ -730,31 +2522,61 @@ pub fn new(path: &str, dest_file: Option<String>, config_path: Option<&str>) ->
     let (comm_enter, args_enter) = util::get_opener(Op::ExitCursorEnter, config_path);
 
     let mut browser = Browser {
-        cursor: 0,
-        window_start: 0,
-        content: Vec::new(),
-        past_dir: Vec::new(),
-        past_cursor: Vec::new(),
-        past_window_start: Vec::new(),
-        current_path: PathBuf::new(),
+        tabs: vec![Tab {
+            refresh_preview: true,
+            ..Tab::default()
+        }],
+        tab_index: 0,
+        copied: Vec::new(),
+        cut: false,
+        pane: None,
         original_path: PathBuf::from("."),
         mode: Mode::Normal,
         search_txt: Vec::new(),
+        search_mode: util::get_search_mode(config_path),
+        compiled_search: CompiledSearch {
+            txt: Vec::new(),
+            mode: SearchMode::Regex,
+            re: None,
+        },
         opener_o: Opener::new(comm_o, args_o),
         opener_enter: Opener::new(comm_enter, args_enter),
         dest_file: (|dest_file| match dest_file {
             Some(df) => Some(PathBuf::from(&df)),
             None => None,
         })(dest_file),
-        search_history: VecDeque::new(),
-        search_history_index: 0,
-        trunc: Vec::new(),
+        dest_format: DestFormat::Lines,
+        search_history: History::load(util::state_file(consts::SEARCH_HISTORY_FILE)),
+        keys: KeyReader::default(),
+        pending: util::Pending::default(),
+        line_editor: LineEditor::default(),
         input_cursor_pos: 0,
         rev_search: false,
-        preview: Vec::new(),
-        refresh_preview: true,
+        chooser: None,
+        bookmarks: Bookmarks::load(util::state_file(consts::BOOKMARK_FILE)),
+        jumplist: JumpList::load(util::state_file(consts::JUMPLIST_FILE)),
+        frecency: Frecency::load(util::state_file(consts::FRECENCY_FILE)),
+        frecency_txt: Vec::new(),
+        frecent: Vec::new(),
+        scrolloff: util::get_scrolloff(config_path),
+        watcher: None,
+        changes: None,
+        events: None,
+        previewer: None,
+        paste_job: None,
+        quit_asked: false,
+        finder: None,
+        find_txt: Vec::new(),
+        find_fuzzy: true,
+        found_scores: Vec::new(),
+        found_ranked: 0,
+        grepper: None,
+        grep_txt: Vec::new(),
+        list: ListView::default(),
+        message: None,
     };
     browser.init(&path);
+    browser.jumplist.visit(browser.tab().current_path.clone());
     browser
 }
 
@@ -763,7 +2585,7 @@ mod test {
     use super::*;
     use crate::util::test::{random_dir_wcontent, CleanupDir, Rand};
     use std::collections::HashSet;
-    use std::fs::{create_dir_all, exists};
+    use std::fs::{create_dir_all, exists, remove_file};
 
     #[test]
     fn test_browser_init() {
@@ -796,7 +2618,7 @@ mod test {
         // we care about the first file
         create_dir_all(&temp_dir).expect(&format!("create dir {} failed", &temp_dir));
         let b = new(&temp_dir, None, None); // browser::new()
-        let past_dir = &b.past_dir;
+        let past_dir = &b.tab().past_dir;
 
         #[allow(unused_assignments)]
         let mut ans = Vec::new();
@@ -842,14 +2664,15 @@ mod test {
             dirs_files.insert(dir.to_string());
         }
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
-        b.read_content(&b.current_path.to_str().unwrap().to_string());
-        let content = b.content.clone();
+        b.read_content(&b.tab().current_path.to_str().unwrap().to_string());
+        b.wait_loaded();
+        let content = b.tab().content.clone();
         let mut dedup: HashSet<String> = HashSet::new();
         for c in content.iter() {
-            if !dirs_files.contains(c) {
+            if !dirs_files.contains(&*c.path) {
                 panic!("incorrect content");
             }
-            dedup.insert(c.clone());
+            dedup.insert(c.path.to_string());
         }
         assert_eq!(
             dedup.len(),
@@ -871,16 +2694,27 @@ mod test {
             dirs_files.insert(dir.to_string());
         }
         let mut b = new("/tmp", None, None);
+        b.wait_loaded();
         let mut cur_pos = 0;
-        for (i, cd) in b.content.iter().enumerate() {
-            if cd == &root_dir {
+        for (i, cd) in b.tab().content.iter().enumerate() {
+            if *cd.path == root_dir {
                 cur_pos = i;
                 break;
             }
         }
         // set browser's cursor
         b.set_cursor_pos_centered(cur_pos);
-        let preview = b.get_preview();
+        // it's read on another thread, it may take longer than it's waited for
+        b.update_preview();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!(b.tab().preview_dir, b.preview_path());
+        // one read for where the cursor isn't anymore is dropped
+        b.previewed((Some(PathBuf::from("/")), (Vec::new(), false)));
+        assert_eq!(b.tab().preview_dir, b.preview_path());
+        let preview = take(&mut b.tab_mut().preview);
+        assert!(!b.tab().preview_truncated);
+        let (names, more) = read_some_names(&b.preview_path().unwrap(), 1);
+        assert_eq!((names.len(), more), (1, true));
         let mut dedup: HashSet<String> = HashSet::new();
         for p in preview {
             if !dirs_files.contains(&p) {
@@ -897,31 +2731,327 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_opener_detach() {
+        let args = ["--new-window", "&"].map(OsString::from).to_vec();
+        let opener = Opener::new(OsString::from("code"), Some(args));
+        assert!(opener.detach);
+        assert_eq!(opener.args, [OsString::from("--new-window")]);
+
+        let opener = Opener::new(OsString::from("vim"), None);
+        assert!(!opener.detach);
+        assert!(opener.args.is_empty());
+    }
+
+    #[test]
+    fn test_opener_args_at() {
+        let opener = Opener::new(
+            OsString::from("/usr/bin/vim"),
+            Some(vec![OsString::from("-R")]),
+        );
+        assert_eq!(opener.args_at(Some(12)), ["-R", "+12"]);
+        assert_eq!(opener.args_at(None), ["-R"]);
+
+        let args = ["-g", "+{line}", "&"].map(OsString::from).to_vec();
+        let opener = Opener::new(OsString::from("gvim"), Some(args));
+        assert_eq!(opener.args_at(Some(12)), ["-g", "+12"]);
+        assert_eq!(opener.args_at(None), ["-g", "+1"]);
+
+        // no idea how to tell it the line
+        let opener = Opener::new(OsString::from("xdg-open"), None);
+        assert!(opener.args_at(Some(12)).is_empty());
+    }
+
+    #[test]
+    fn test_grep_results() {
+        let (files, _, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        std::fs::write(root.join(&files[0]), "a\nneedle\nNEEDLE\n").unwrap();
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.grep_txt = "needle".chars().collect::<Vec<char>>();
+        let re = matcher::search_regex(&b.grep_txt, SearchMode::Regex).unwrap();
+        b.grepper = Some(Grepper::start(b.tab().current_path.clone(), re, None));
+        while b.grepper.as_ref().unwrap().running {
+            b.grepper.as_mut().unwrap().poll();
+        }
+        b.update_hits();
+        // smart-case like the search
+        assert_eq!(
+            b.list.items,
+            [
+                format!("{}:2: needle", files[0]),
+                format!("{}:3: NEEDLE", files[0])
+            ]
+        );
+        assert_eq!(
+            b.list.title,
+            format!(
+                "grep needle: 2 hits in {} files  <enter> open  esc close",
+                files.len()
+            )
+        );
+    }
+
+    #[test]
+    fn test_toggle_mark() {
+        let (_, _, root_dir, _cd) = random_dir_wcontent();
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        b.top();
+        let mut first = b.tab().current_path.clone();
+        first.push(&*b.tab().content[0].path);
+        b.toggle_mark();
+        assert_eq!(b.tab().marked, [first]);
+        assert_eq!(b.tab().cursor, 1);
+        b.up();
+        b.toggle_mark();
+        assert!(b.tab().marked.is_empty());
+    }
+
+    #[test]
+    fn test_dest_json() {
+        let (_, _, root_dir, _cd) = random_dir_wcontent();
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        b.top();
+        b.toggle_mark();
+        let current_path = b.tab().current_path.to_str().unwrap().to_string();
+        let first = format!("{}/{}", current_path, b.tab().content[0].path);
+        let second = format!("{}/{}", current_path, b.tab().content[1].path);
+        assert_eq!(
+            b.dest_json(Exit::Quit, &PathBuf::from(".")),
+            format!(
+                "{{\"reason\":\"quit\",\"path\":\".\",\"current_path\":\"{}\",\"cursor\":\"{}\",\"marked\":[\"{}\"]}}\n",
+                current_path, second, first
+            )
+        );
+    }
+
+    #[test]
+    fn test_set_cursor_on() {
+        let (files, _, root_dir, _cd) = random_dir_wcontent();
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        let f = &files[files.len() - 1];
+        b.set_cursor_on(f);
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, f);
+        let h = get_height();
+        assert!(b.tab().cursor - b.tab().window_start <= h / 2);
+    }
+
+    #[test]
+    fn test_jump() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        let mut b = new("/tmp", None, None);
+        let target = PathBuf::from(format!("/tmp/{}/{}", root_dir, dirs[0]))
+            .canonicalize()
+            .unwrap();
+        b.bookmarks.set('a', target.clone());
+        b.list = ListView::new("bookmarks", b.bookmarks.items());
+        b.bookmark_list(Op::ExitCursorEnter);
+        assert_eq!(b.tab().current_path, target);
+        // h goes back to the parent like it was opened from the start
+        b.left();
+        b.wait_loaded();
+        assert_eq!(Some(b.tab().current_path.as_path()), target.parent());
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, &dirs[0]);
+
+        b.jump(Path::new("/nonexistent-ts-test"));
+        assert_eq!(Some(b.tab().current_path.as_path()), target.parent());
+        assert!(b.message.is_some());
+    }
+
+    #[test]
+    fn test_jump_back_forward() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        let root = b.tab().current_path.clone();
+        b.set_cursor_on(&dirs[0]);
+        b.right();
+        let mut child = root.clone();
+        child.push(&dirs[0]);
+        b.jump(Path::new("/tmp"));
+        b.jump_back();
+        assert_eq!(b.tab().current_path, child);
+        b.jump_back();
+        b.wait_loaded();
+        assert_eq!(b.tab().current_path, root);
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, &dirs[0]);
+        b.jump_forward();
+        assert_eq!(b.tab().current_path, child);
+        // stacks are rebuilt, h works as usual
+        b.left();
+        assert_eq!(b.tab().current_path, root);
+    }
+
+    #[test]
+    fn test_update_frecent() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        let root = b.tab().current_path.clone();
+        b.set_cursor_on(&dirs[0]);
+        b.right();
+        b.left();
+        b.wait_loaded();
+        b.set_cursor_on(&dirs[1]);
+        b.right();
+        b.right();
+        b.left();
+
+        // visited directories are ranked as the Z prompt is typed in
+        b.mode = Mode::Frecency;
+        b.frecency_txt = dirs[1].chars().collect::<Vec<char>>();
+        b.update_frecent();
+        assert_eq!(b.frecent[0], root.join(&dirs[1]));
+        assert_eq!(b.list.items[0], root.join(&dirs[1]).to_string_lossy());
+        assert_eq!(b.prompt_txt(), &b.frecency_txt);
+        b.frecency_txt = "/nonexistent-ts-test".chars().collect::<Vec<char>>();
+        b.update_frecent();
+        assert!(b.list.items.is_empty());
+    }
+
+    #[test]
+    fn test_filter() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        let all = b.tab().content.clone();
+        b.tab_mut().filter = Some(Filter {
+            fuzzy: true,
+            all: all.clone(),
+            positions: Vec::new(),
+            scores: Vec::new(),
+        });
+        b.tab_mut().filter_txt = dirs[0].chars().collect::<Vec<char>>();
+        b.apply_filter();
+        assert_eq!(*b.tab().content[0].path, dirs[0]);
+        assert!(b.tab().content.len() < all.len());
+        let positions = b.tab().filter.as_ref().unwrap().positions[0].clone();
+        assert_eq!(
+            positions,
+            (0..dirs[0].chars().count()).collect::<Vec<usize>>()
+        );
+
+        // regex, everything matches '.'
+        b.tab_mut().filter.as_mut().unwrap().fuzzy = false;
+        b.tab_mut().filter_txt = vec!['.'];
+        b.apply_filter();
+        assert_eq!(b.tab().content, all);
+        b.tab_mut().filter_txt = vec!['('];
+        b.apply_filter();
+        assert_eq!(b.tab().content, all);
+        b.tab_mut().filter_txt = format!("^{}$", dirs[1]).chars().collect::<Vec<char>>();
+        b.apply_filter();
+        assert_eq!(b.tab().content, [Entry::from(dirs[1].clone())]);
+
+        // entering a directory clears the filter
+        b.right();
+        assert!(b.tab().filter.is_none());
+        b.left();
+        b.wait_loaded();
+        assert_eq!(b.tab().content, all);
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, &dirs[1]);
+
+        // so does the clear key, the cursor stays on the same entry
+        b.tab_mut().filter = Some(Filter {
+            fuzzy: true,
+            all: all.clone(),
+            positions: Vec::new(),
+            scores: Vec::new(),
+        });
+        b.tab_mut().filter_txt = dirs[0].chars().collect::<Vec<char>>();
+        b.apply_filter();
+        b.clear_filter();
+        assert_eq!(b.tab().content, all);
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, &dirs[0]);
+        assert!(b.tab().filter_txt.is_empty());
+
+        // what's read while it's listed is merged into the matches the way they'd be ranked all
+        // at once, and the cursor stays where it is
+        b.tab_mut().filter = Some(Filter {
+            fuzzy: true,
+            all: Vec::new(),
+            positions: Vec::new(),
+            scores: Vec::new(),
+        });
+        b.tab_mut().filter_txt = vec!['f'];
+        b.tab_mut().content = Vec::new();
+        b.add_content(all[..all.len() / 2].to_vec());
+        b.tab_mut().cursor = 1;
+        b.add_content(all[all.len() / 2..].to_vec());
+        assert_eq!(b.tab().cursor, 1);
+        let merged = b.tab().content.clone();
+        let positions = b.tab().filter.as_ref().unwrap().positions.clone();
+        b.apply_filter();
+        assert_eq!(b.tab().content, merged);
+        assert_eq!(b.tab().filter.as_ref().unwrap().positions, positions);
+    }
+
+    #[test]
+    fn test_find() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        let nested = format!("{}/f-nested", dirs[0]);
+        File::create(root.join(&nested)).unwrap();
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.mode = Mode::Find;
+        b.finder = Some(Finder::start(b.tab().current_path.clone(), None));
+        while b.finder.as_ref().unwrap().running {
+            b.finder.as_mut().unwrap().poll();
+        }
+        b.find_txt = "f-nested".chars().collect::<Vec<char>>();
+        b.update_found();
+        assert_eq!(b.list.items, std::slice::from_ref(&nested));
+        assert_eq!(
+            b.counts(),
+            Some(String::from("1/") + &b.finder.as_ref().unwrap().found.len().to_string())
+        );
+        // what's found later is merged in, what matches as well comes after
+        let later = format!("{}/f-nested", dirs[1]);
+        b.finder.as_mut().unwrap().found.push(later.clone());
+        b.rank_found();
+        assert_eq!(b.list.items, [nested, later]);
+
+        // the stacks are rebuilt, h goes back up to the root
+        b.reveal(&root.join(&b.list.items[0]));
+        b.wait_loaded();
+        assert_eq!(b.tab().current_path, root.join(&dirs[0]));
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, "f-nested");
+        b.left();
+        b.wait_loaded();
+        assert_eq!(b.tab().current_path, root);
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, &dirs[0]);
+    }
+
     #[test]
     fn test_top() {
         let (_, _, root_dir, _cd) = random_dir_wcontent();
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
         b.top();
-        assert_eq!(b.cursor, 0);
-        assert_eq!(b.window_start, 0);
+        assert_eq!(b.tab().cursor, 0);
+        assert_eq!(b.tab().window_start, 0);
     }
 
     #[test]
     fn test_bottom() {
         let (_, _, root_dir, _cd) = random_dir_wcontent();
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
         b.bottom();
-        assert_eq!(b.cursor, b.content.len() - 1);
+        assert_eq!(b.tab().cursor, b.tab().content.len() - 1);
     }
 
     #[test]
     fn test_up() {
         let (_, _, root_dir, _cd) = random_dir_wcontent();
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
         b.bottom();
-        let cur_pos1 = b.cursor;
+        let cur_pos1 = b.tab().cursor;
         b.up();
-        let cur_pos2 = b.cursor;
+        let cur_pos2 = b.tab().cursor;
         // guarantee to have some files and dirs
         assert_eq!(cur_pos1, cur_pos2 + 1);
     }
@@ -930,14 +3060,359 @@ mod test {
     fn test_down() {
         let (_, _, root_dir, _cd) = random_dir_wcontent();
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
         b.top();
-        let cur_pos1 = b.cursor;
+        let cur_pos1 = b.tab().cursor;
         b.down();
-        let cur_pos2 = b.cursor;
+        let cur_pos2 = b.tab().cursor;
         // guarantee to have some files and dirs
         assert_eq!(cur_pos1 + 1, cur_pos2);
     }
 
+    #[test]
+    fn test_tabs() {
+        let (files, dirs, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        let canonical = b.tab().current_path.clone();
+        b.set_cursor_on(&files[0]);
+        assert!(b.tab_names().is_empty());
+
+        // a new tab starts where the current one is and goes its own way
+        b.new_tab();
+        b.wait_loaded();
+        assert_eq!((b.tabs.len(), b.tab_index), (2, 1));
+        // the tab bar takes a row
+        assert_eq!(b.height(), get_height() - 1);
+        assert!(!has_tab_bar(2, 2));
+        assert_eq!(b.cursor_name(), files[0]);
+        b.set_cursor_on(&dirs[0]);
+        b.right();
+        assert_eq!(b.tab_names(), [root_dir.clone(), dirs[0].clone()]);
+        b.switch_tab(0);
+        assert_eq!(b.tab().current_path, canonical);
+        assert_eq!(b.cursor_name(), files[0]);
+
+        // copied in one tab, pasted in another, a second paste doesn't overwrite the first
+        b.copy(false);
+        b.switch_tab(1);
+        b.paste();
+        b.poll_jobs(Duration::from_secs(5));
+        b.paste();
+        b.poll_jobs(Duration::from_secs(5));
+        assert!(root.join(&dirs[0]).join(&files[0]).is_file());
+        assert_eq!(b.cursor_name(), format!("{}_", files[0]));
+        assert!(root.join(&files[0]).is_file());
+
+        // a cut is moved, and pasted only once
+        b.copy(true);
+        b.switch_tab(0);
+        b.paste();
+        b.poll_jobs(Duration::from_secs(5));
+        assert!(root.join(format!("{}_", files[0])).is_file());
+        assert!(!root.join(&dirs[0]).join(format!("{}_", files[0])).exists());
+        assert!(b.copied.is_empty());
+
+        // a cancelled paste stops before the next path
+        let cancel = AtomicBool::new(true);
+        let pasted = paste_paths(&[root.join(&files[1])], &root, false, &cancel);
+        assert!(pasted.first.is_none() && pasted.cancelled);
+        assert!(!root.join(format!("{}_", files[1])).exists());
+        // what isn't moved of a cut can be pasted again
+        b.set_cursor_on(&files[1]);
+        b.copy(true);
+        let pasted = paste_paths(&b.copied, &root.join(&dirs[0]), true, &cancel);
+        b.pasted(&root.join(&dirs[0]), pasted);
+        assert_eq!(b.copied, [root.join(&files[1])]);
+        assert_eq!(b.message.as_deref(), Some("Paste cancelled"));
+
+        // moving into the same directory leaves it, every failure is counted
+        let cancel = AtomicBool::new(false);
+        let pasted = paste_paths(&[root.join(&files[1])], &root, true, &cancel);
+        assert_eq!(pasted.first.as_deref(), Some(files[1].as_str()));
+        assert!(root.join(&files[1]).is_file());
+        assert!(!root.join(format!("{}_", files[1])).exists());
+        let missing = [root.join("missing-a"), root.join("missing-b")];
+        let pasted = paste_paths(&missing, &root.join(&dirs[0]), false, &cancel);
+        assert_eq!(pasted.errors.len(), 2);
+        b.pasted(&root, pasted);
+        assert!(b
+            .message
+            .as_ref()
+            .is_some_and(|m| m.ends_with("and 1 more failed")));
+
+        assert_eq!(b.tab_jumps()[1].path, canonical.join(&dirs[0]));
+        b.close_tab();
+        assert_eq!((b.tabs.len(), b.tab_index), (1, 0));
+        assert_eq!(b.tab().current_path, canonical.join(&dirs[0]));
+        b.close_tab();
+        assert_eq!(b.tabs.len(), 1);
+        assert!(b.message.is_some());
+    }
+
+    #[test]
+    fn test_dual_pane() {
+        let (files, dirs, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        assert!(b.other_pane().is_none());
+
+        // the second pane starts where the first one is
+        b.toggle_dual();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!((b.tabs.len(), b.tab_index), (2, 0));
+        let other = b.other_pane().expect("no other pane");
+        assert_eq!(other.current_path, b.tab().current_path);
+        b.switch_tab(1);
+        b.set_cursor_on(&dirs[0]);
+        b.right();
+        b.switch_tab(0);
+
+        // F5 copies into the other pane, F6 moves there
+        b.set_cursor_on(&files[0]);
+        b.paste_to_other(false);
+        b.poll_jobs(Duration::from_secs(5));
+        assert!(root.join(&files[0]).is_file());
+        assert!(root.join(&dirs[0]).join(&files[0]).is_file());
+        assert!(b
+            .other_pane()
+            .unwrap()
+            .content
+            .iter()
+            .any(|e| *e.path == files[0]));
+        b.set_cursor_on(&files[1]);
+        b.toggle_mark();
+        b.paste_to_other(true);
+        b.poll_jobs(Duration::from_secs(5));
+        assert!(!root.join(&files[1]).exists());
+        assert!(root.join(&dirs[0]).join(&files[1]).is_file());
+        assert!(!b.tab().content.iter().any(|e| *e.path == files[1]));
+
+        // the other pane stays while going through the other tabs, and swaps with the current
+        // one when it's gone to
+        b.new_tab();
+        assert_eq!((b.tab_index, b.pane), (1, Some(2)));
+        b.switch_tab(0);
+        assert_eq!((b.tab_index, b.pane), (0, Some(2)));
+        b.reload_panes();
+        b.switch_tab(2);
+        assert_eq!((b.tab_index, b.pane), (2, Some(0)));
+        // closing a pane leaves the other one
+        b.close_tab();
+        assert_eq!((b.tabs.len(), b.tab_index, b.pane), (2, 0, None));
+
+        b.toggle_dual();
+        assert_eq!(b.pane, Some(1));
+        b.toggle_dual();
+        assert!(b.other_pane().is_none());
+    }
+
+    #[test]
+    fn test_tree() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        create_dir_all(root.join(&dirs[0]).join("inner")).expect("failed to create dir");
+        File::create(root.join(&dirs[0]).join("inner").join("x")).expect("failed to create file");
+        File::create(root.join(&dirs[0]).join("y")).expect("failed to create file");
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        let len = b.tab().content.len();
+        b.set_cursor_on(&dirs[0]);
+        b.toggle_tree();
+        b.poll_jobs(Duration::from_secs(5));
+
+        // expanded in place, the entries are one level deeper
+        b.expand();
+        b.poll_jobs(Duration::from_secs(5));
+        let inner = format!("{}/inner", dirs[0]);
+        let index = b.tab().cursor;
+        assert_eq!(b.tab().content.len(), len + 2);
+        assert_eq!(*b.tab().content[index + 1].path, inner);
+        assert_eq!(b.tab().content[index + 2].depth, 1);
+        b.down();
+        b.expand();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!(*b.tab().content[index + 2].path, format!("{}/x", inner));
+        assert_eq!(b.tab().content[index + 2].depth, 2);
+
+        // the search goes by the names
+        b.search_txt = "^x$".chars().collect();
+        b.compile_search();
+        assert_eq!(b.search_positions(index + 2), Some(vec![0]));
+
+        // collapsed from inside
+        b.down();
+        b.collapse();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!(b.cursor_name(), inner);
+        assert_eq!(b.tab().content.len(), len + 2);
+
+        // what's expanded in a collapsed directory is remembered
+        b.expand();
+        b.poll_jobs(Duration::from_secs(5));
+        b.up();
+        b.collapse();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!((b.tab().content.len(), b.tab().cursor), (len, index));
+        b.expand();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!(b.tab().content.len(), len + 3);
+
+        // an expanded directory is entered wherever it is in the tree, and left back to it
+        b.down();
+        b.expand();
+        b.poll_jobs(Duration::from_secs(5));
+        assert!(b.tab().current_path.ends_with(&inner));
+        b.left();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!(b.tab().current_path, root);
+        assert_eq!(b.cursor_name(), inner);
+        b.up();
+
+        // the filter keeps the tree in order, and the directories the matches are in
+        b.tab_mut().filter = Some(Filter {
+            fuzzy: false,
+            all: b.tab().content.clone(),
+            positions: Vec::new(),
+            scores: Vec::new(),
+        });
+        b.tab_mut().filter_txt = "^x$".chars().collect();
+        b.apply_filter();
+        let paths = b
+            .tab()
+            .content
+            .iter()
+            .map(|e| &*e.path)
+            .collect::<Vec<&str>>();
+        assert_eq!(paths, [&dirs[0], &inner, &format!("{}/x", inner)]);
+        let positions = &b.tab().filter.as_ref().unwrap().positions;
+        assert_eq!(positions, &[vec![], vec![], vec![0]]);
+        b.clear_filter();
+
+        // the cursor goes up the tree when it's off
+        b.set_cursor_on(&format!("{}/x", inner));
+        b.toggle_tree();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!(b.tab().content.len(), len);
+        assert_eq!(b.cursor_name(), dirs[0]);
+    }
+
+    #[test]
+    fn test_reload() {
+        let (files, dirs, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        b.set_cursor_on(&files[1]);
+        // entries before the cursor come and go, the cursor stays on its entry
+        File::create(root.join("0-new")).expect("failed to create file");
+        remove_file(root.join(&files[0])).expect("failed to remove file");
+        b.reload();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!(b.cursor_name(), files[1]);
+        assert_eq!(&*b.tab().content[0].path, "0-new");
+        assert!(!b.tab().content.iter().any(|e| *e.path == files[0]));
+        // the entry under the cursor is gone, the cursor stays where it was
+        let cursor = b.tab().cursor;
+        remove_file(root.join(&files[1])).expect("failed to remove file");
+        b.reload();
+        b.poll_jobs(Duration::from_secs(5));
+        assert_eq!(b.tab().cursor, cursor.min(b.tab().content.len() - 1));
+
+        b.set_cursor_on(&dirs[0]);
+        assert_eq!(
+            b.shown_dirs(),
+            [
+                b.tab().current_path.clone(),
+                b.tab().current_path.join(&dirs[0])
+            ]
+        );
+
+        // a directory that takes long to read is listed as it's read, and sorted at the end
+        File::create(root.join("0-later")).expect("failed to create file");
+        let mut loader = Loader::start(b.tab().current_path.clone(), None, true, None);
+        loader.select = Some((dirs[1].clone(), 0));
+        b.tab_mut().content = Vec::new();
+        b.top();
+        b.tab_mut().loader = Some(loader);
+        assert_eq!(b.loading(), Some(0));
+        assert!(b.poll_jobs(Duration::from_secs(5)));
+        assert_eq!(b.loading(), None);
+        assert_eq!(&*b.tab().content[0].path, "0-later");
+        assert_eq!(b.cursor_name(), dirs[1]);
+        // read again meanwhile, the cursor still goes where it was meant to
+        b.tab_mut().loader = Some(Loader::start(
+            b.tab().current_path.clone(),
+            None,
+            true,
+            None,
+        ));
+        b.tab_mut().content = Vec::new();
+        b.top();
+        b.select(&dirs[0]);
+        b.reload();
+        assert!(b.poll_jobs(Duration::from_secs(5)));
+        assert_eq!(b.cursor_name(), dirs[0]);
+        // another tab's is read into that tab
+        b.new_tab();
+        b.wait_loaded();
+        File::create(root.join("0-first")).expect("failed to create file");
+        b.in_tab(0, Browser::reload);
+        assert!(b.poll_jobs(Duration::from_secs(5)));
+        assert_eq!(&*b.tabs[0].content[0].path, "0-first");
+        assert_eq!(&*b.tab().content[0].path, "0-later");
+    }
+
+    #[test]
+    fn test_motions() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        for i in 10..40 {
+            File::create(format!("/tmp/{}/e-{}", root_dir, i)).expect("failed to create file");
+        }
+        let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
+        b.scrolloff = 2;
+        let len = b.tab().content.len();
+
+        // past either end wraps around
+        b.move_by(-1);
+        assert_eq!((b.tab().cursor, b.tab().window_start), (len - 1, len - 9));
+        b.move_by(1);
+        assert_eq!((b.tab().cursor, b.tab().window_start), (0, 0));
+        // scrolloff lines stay below the cursor
+        b.move_by(7);
+        assert_eq!((b.tab().cursor, b.tab().window_start), (7, 1));
+
+        b.screen_jump(Op::ScreenTop, 1);
+        assert_eq!(b.tab().cursor, 3);
+        b.screen_jump(Op::ScreenBottom, 2);
+        assert_eq!(b.tab().cursor, 6);
+        b.screen_jump(Op::ScreenMiddle, 1);
+        assert_eq!(b.tab().cursor, 5);
+
+        b.scroll_cursor(Op::ScrollTop);
+        assert_eq!(b.tab().window_start, 3);
+        b.scroll_cursor(Op::ScrollBottom);
+        assert_eq!(b.tab().window_start, 0);
+        b.scroll(3);
+        assert_eq!((b.tab().cursor, b.tab().window_start), (5, 3));
+        b.page(1);
+        assert_eq!((b.tab().cursor, b.tab().window_start), (12, 10));
+        b.page(-2);
+        assert_eq!((b.tab().cursor, b.tab().window_start), (6, 0));
+        // the window is at the start already
+        b.page(-1);
+        assert_eq!((b.tab().cursor, b.tab().window_start), (0, 0));
+
+        b.find_char('E', 2);
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, "e-11");
+        assert_eq!(b.tab().cursor, dirs.len() + 1);
+        b.find_char('~', 1);
+        assert_eq!(b.tab().cursor, dirs.len() + 1);
+        assert!(b.message.is_some());
+    }
+
     #[test]
     fn test_left() {
         let (_, dirs, root_dir, _cd) = random_dir_wcontent();
@@ -945,7 +3420,7 @@ mod test {
         let mut b = new(&format!("/tmp/{}/{}", root_dir, target), None, None);
         b.left();
         assert_eq!(
-            b.current_path.to_str().unwrap(),
+            b.tab().current_path.to_str().unwrap(),
             if cfg!(target_os = "macos") {
                 format!("/private/tmp/{}", root_dir)
             } else {
@@ -959,21 +3434,94 @@ mod test {
         let (_, dirs, root_dir, _cd) = random_dir_wcontent();
         let target = &dirs[0];
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
-        for (i, dir) in b.content.iter().enumerate() {
-            if dir == target {
+        b.wait_loaded();
+        for (i, dir) in b.tab().content.iter().enumerate() {
+            if *dir.path == *target {
                 b.set_cursor_pos_centered(i);
                 break;
             }
         }
         b.right();
         assert_eq!(
-            b.current_path.to_str().unwrap(),
+            b.tab().current_path.to_str().unwrap(),
             if cfg!(target_os = "macos") {
                 format!("/private/tmp/{}/{}", root_dir, target)
             } else {
                 format!("/tmp/{}/{}", root_dir, target)
             }
         );
+
+        // with a count, as far as it can go
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        create_dir_all(root.join("chain/a/b")).expect("failed to create dir");
+        b.jump(&root);
+        b.wait_loaded();
+        b.set_cursor_on("chain");
+        b.enter_dirs(5);
+        assert_eq!(b.tab().current_path, root.join("chain/a/b"));
+        b.leave_dirs(2);
+        b.wait_loaded();
+        assert_eq!(b.tab().current_path, root.join("chain"));
+        assert_eq!(b.cursor_name(), "a");
+        // what's left of the count goes on once the directory it's gone into is read
+        b.right();
+        let mut loader = Loader::start(root.join("chain/a"), None, true, None);
+        loader.enter = 1;
+        b.tab_mut().content = Vec::new();
+        b.tab_mut().loader = Some(loader);
+        assert!(b.poll_jobs(Duration::from_secs(5)));
+        assert_eq!(b.tab().current_path, root.join("chain/a/b"));
+    }
+
+    #[test]
+    fn test_mouse() {
+        let root = PathBuf::from(format!("/tmp/ts-test-mouse-{}", Rand::new().rand_str()));
+        let _cd = CleanupDir {
+            dir: root.to_string_lossy().to_string(),
+        };
+        create_dir_all(root.join("d")).expect("failed to create dir");
+        for name in ["d/a", "d/b"] {
+            File::create(root.join(name)).expect("failed to create file");
+        }
+        // d, f00 ... f19
+        for i in 0..20 {
+            File::create(root.join(format!("f{:02}", i))).expect("failed to create file");
+        }
+        let mut b = new(&root.to_string_lossy(), None, None);
+        b.wait_loaded();
+        let mut canvas = canvas::new(None);
+        canvas.width = 20;
+        canvas.height = get_height() + 1;
+        let click = |kind, row, col| Mouse { kind, row, col };
+
+        assert!(b.mouse(click(MouseKind::Click, 1, 3), &canvas) == Op::Noop);
+        assert_eq!(b.tab().cursor, 1);
+        b.mouse(click(MouseKind::Click, 8, 3), &canvas);
+        assert_eq!(b.tab().cursor, 8);
+        assert!(b.mouse(click(MouseKind::Click, get_height(), 3), &canvas) == Op::Search);
+
+        // the wheel scrolls, the cursor stays in the window
+        b.top();
+        b.mouse(click(MouseKind::WheelDown, 0, 15), &canvas);
+        assert_eq!((b.tab().window_start, b.tab().cursor), (3, 3));
+        b.mouse(click(MouseKind::WheelUp, 0, 15), &canvas);
+        assert_eq!((b.tab().window_start, b.tab().cursor), (0, 3));
+
+        // a click in the preview of a file does nothing, even if the preview isn't cleared yet
+        b.tab_mut().preview = vec![String::from("x"); 5];
+        b.mouse(click(MouseKind::Click, 4, 15), &canvas);
+        assert_eq!(
+            (b.tab().current_path.clone(), b.tab().cursor),
+            (root.clone(), 3)
+        );
+
+        // a click in the preview of a directory goes into it, on the entry clicked
+        b.top();
+        b.tab_mut().preview = preview_of(b.preview_path()).0;
+        b.mouse(click(MouseKind::Click, 1, 15), &canvas);
+        b.wait_loaded();
+        assert_eq!(b.tab().current_path, root.join("d"));
+        assert_eq!(&*b.tab().content[b.tab().cursor].path, "b");
     }
 
     #[test]
@@ -982,9 +3530,9 @@ mod test {
         // content is guaranteed to not be empty
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
         b.bottom();
-        let cursor_pos1 = b.cursor;
+        let cursor_pos1 = b.tab().cursor;
         b.pageup();
-        let cursor_pos2 = b.cursor;
+        let cursor_pos2 = b.tab().cursor;
         let half_page = get_height() / 2;
         let expected = if cursor_pos1 < half_page {
             0
@@ -998,13 +3546,14 @@ mod test {
     fn test_pagedown() {
         let (_, _, root_dir, _cd) = random_dir_wcontent();
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
+        b.wait_loaded();
         b.top();
-        let cursor_pos1 = b.cursor;
+        let cursor_pos1 = b.tab().cursor;
         b.pagedown();
-        let cursor_pos2 = b.cursor;
+        let cursor_pos2 = b.tab().cursor;
         let half_page = get_height() / 2;
-        let expected = if cursor_pos1 + half_page >= b.content.len() {
-            b.content.len() - 1
+        let expected = if cursor_pos1 + half_page >= b.tab().content.len() {
+            b.tab().content.len() - 1
         } else {
             cursor_pos1 + half_page
         };
@@ -1018,24 +3567,49 @@ mod test {
         let mut rand = Rand::new();
         let f = files[rand.rand_uint(0, files.len() - 1)].clone();
         let mut b = new(&format!("/tmp/{}", root_dir), None, None);
-        let content = b.content.clone();
+        b.wait_loaded();
+        let content = b.tab().content.clone();
         let mut answer = content.len();
         for (i, c) in content.iter().enumerate() {
-            if c == &f {
+            if *c.path == f {
                 answer = i;
                 break;
             }
         }
         b.search_txt = f.chars().collect::<Vec<char>>();
-        b.next_match(b.cursor, false);
-        assert_eq!(b.cursor, answer);
+        b.next_match(b.tab().cursor, false);
+        assert_eq!(b.tab().cursor, answer);
 
         // regex
         b.search_txt = f.chars().take(f.len() / 2).collect::<Vec<char>>();
         b.search_txt.push('.');
         b.search_txt.push('*');
-        b.cursor = 0;
-        b.next_match(b.cursor, false);
-        assert_eq!(b.cursor, answer);
+        b.tab_mut().cursor = 0;
+        b.next_match(b.tab().cursor, false);
+        assert_eq!(b.tab().cursor, answer);
+
+        // the regex is compiled once per search text, the cursor is on the only match
+        b.search_txt = f.chars().collect::<Vec<char>>();
+        assert!(b.search_re().is_none());
+        b.next_match(b.tab().cursor, false);
+        assert!(b.search_re().is_some());
+        assert_eq!(b.counts(), Some(String::from("[1/1]")));
+        b.tab_mut().cursor = (answer + 1) % b.tab().content.len();
+        assert_eq!(b.counts(), Some(String::from("[?/1]")));
+        // the matches are looked for once, until the content changes
+        b.update_search_matches();
+        assert_eq!(b.tab().search_matches, Some(vec![answer]));
+        b.apply_content(b.tab().content.clone());
+        assert_eq!(b.tab().search_matches, None);
+
+        // an invalid pattern doesn't move the cursor
+        b.tab_mut().cursor = 0;
+        b.search_txt = vec!['('];
+        b.next_match(b.tab().cursor, false);
+        assert_eq!(b.tab().cursor, 0);
+        assert_eq!(b.counts(), Some(String::from("[invalid pattern]")));
+        b.search_mode = SearchMode::Literal;
+        b.next_match(b.tab().cursor, false);
+        assert_eq!(b.counts(), Some(String::from("[?/0]")));
     }
 }
@@ -7,7 +7,9 @@
 ╚═══════════════════════════════════════════════════════════════════════*/
 
 use crate::browser;
-use crate::ops::Mode;
+use crate::list::ListView;
+use crate::ops::{Mode, SearchMode};
+use crate::tab::Tab;
 use crate::theme;
 use crate::util;
 use crate::widechar_width::{WcLookupTable, WcWidth};
@@ -15,6 +17,10 @@ use crate::widechar_width::{WcLookupTable, WcWidth};
 use std::io::stdout;
 use std::io::Write;
 
+// matched characters are bold and underlined on top of the colors of the row
+const MATCH_ON: &str = "\x1b[1;4m";
+const MATCH_OFF: &str = "\x1b[22;24m";
+
 pub struct Canvas {
     pub height: usize,
     pub width: usize,
@@ -22,6 +28,40 @@ pub struct Canvas {
     utf8_table: WcLookupTable,
     pub bottom_start: usize, // the left border of the bottom bar text
     add_algnmt: bool,
+    // ratios of the column widths, see 'columns' in the config file
+    columns: Vec<usize>,
+    // whether the first line is the tab bar, as of the last draw
+    tab_bar: bool,
+    // whether the focused pane is the left one in the dual-pane mode, as of the last draw
+    dual: Option<bool>,
+}
+
+// a column narrower than this is left out
+const MIN_WIDTH: usize = 8;
+
+/// The columns of the window
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Pane {
+    Parent,
+    Current,
+    Preview,
+    // the pane without the focus in the dual-pane mode
+    Other,
+}
+
+/// What is under the mouse
+#[derive(PartialEq, Debug)]
+pub enum Hit {
+    // a row of the parent column
+    Parent(usize),
+    // a row of the current directory
+    Current(usize),
+    // a row of the preview column
+    Preview(usize),
+    // a row of the pane without the focus
+    Other(usize),
+    BottomBar,
+    Nothing,
 }
 
 fn csi(s: &str) -> String {
@@ -32,29 +72,92 @@ fn csi(s: &str) -> String {
 
 fn is_dir(do_preview: bool, i: usize, browser: &browser::Browser) -> bool {
     if !do_preview {
-        if i + browser.window_start >= browser.content.len() {
+        if i + browser.tab().window_start >= browser.tab().content.len() {
             return false;
         } else {
-            let mut tmp_path = browser.current_path.clone();
-            tmp_path.push(&browser.content[i + browser.window_start]);
+            let mut tmp_path = browser.tab().current_path.clone();
+            tmp_path.push(&*browser.tab().content[i + browser.tab().window_start].path);
             return tmp_path.is_dir();
         }
     } else {
-        if browser.cursor >= browser.content.len() {
+        if browser.tab().cursor >= browser.tab().content.len() {
             return false;
         } else {
-            let mut tmp_path = browser.current_path.clone();
-            tmp_path.push(&browser.content[browser.cursor]);
-            if i >= browser.preview.len() {
+            let mut tmp_path = browser.tab().current_path.clone();
+            tmp_path.push(&*browser.tab().content[browser.tab().cursor].path);
+            if i >= browser.tab().preview.len() {
                 return false;
             } else {
-                tmp_path.push(&browser.preview[i]);
+                tmp_path.push(&browser.tab().preview[i]);
                 return tmp_path.is_dir();
             }
         }
     }
 }
 
+/// What the bottom bar prompt starts with, empty when there's no prompt
+fn prompt_prefix(browser: &browser::Browser) -> &'static str {
+    match browser.mode {
+        Mode::Search => match browser.search_mode {
+            SearchMode::Regex => "/",
+            SearchMode::Literal => "literal /",
+            SearchMode::Glob => "glob /",
+        },
+        Mode::RevSearch => match browser.search_mode {
+            SearchMode::Regex => "?",
+            SearchMode::Literal => "literal ?",
+            SearchMode::Glob => "glob ?",
+        },
+        Mode::Frecency => "z ",
+        Mode::Filter => match &browser.tab().filter {
+            Some(filter) if !filter.fuzzy => "filter regex: ",
+            _ => "filter: ",
+        },
+        Mode::Find if !browser.find_fuzzy => "find regex: ",
+        Mode::Find => "find: ",
+        Mode::Grep => "grep: ",
+        _ => "",
+    }
+}
+
+/// Whether the j-th character of the i-th row in the left window is matched by the filter or the
+/// search
+fn is_match(
+    i: usize,
+    j: usize,
+    browser: &browser::Browser,
+    search_positions: &[Option<Vec<usize>>],
+) -> bool {
+    let filter = browser
+        .tab()
+        .filter
+        .as_ref()
+        .and_then(|filter| filter.positions.get(i + browser.tab().window_start));
+    let search = search_positions.get(i).and_then(|p| p.as_ref());
+    filter.is_some_and(|positions| positions.contains(&j))
+        || search.is_some_and(|positions| positions.contains(&j))
+}
+
+/// The row of an entry in a listing, its name after the guides in the tree view
+fn listed(tab: &Tab, guides: &[String], index: usize) -> Option<String> {
+    let entry = tab.content.get(index)?;
+    Some(match guides.get(index) {
+        Some(guide) => format!("{}{}", guide, entry.name()),
+        None => entry.path.to_string(),
+    })
+}
+
+fn is_marked(i: usize, browser: &browser::Browser) -> bool {
+    if browser.tab().marked.is_empty()
+        || i + browser.tab().window_start >= browser.tab().content.len()
+    {
+        return false;
+    }
+    let mut tmp_path = browser.tab().current_path.clone();
+    tmp_path.push(&*browser.tab().content[i + browser.tab().window_start].path);
+    browser.tab().marked.contains(&tmp_path)
+}
+
 impl Canvas {
     /// Set the internel pixel (char) representation
     fn set_pixel(&self, pixels: &mut Vec<Vec<char>>, i: usize, j: usize, c: char) {
@@ -71,26 +174,35 @@ impl Canvas {
     /// Get the index where the bottom line text should be cropped
     fn bottom_line_configure(&mut self, browser: &browser::Browser) -> String {
         let mut bottom_line = String::new();
-        let mut special_char = false;
-
-        if matches!(browser.mode, Mode::Search) || matches!(browser.mode, Mode::RevSearch) {
-            special_char = true; // we will prepend the slash later
-            bottom_line.push_str(&browser.search_txt.iter().collect::<String>());
+        // we will prepend the prefix, e.g. the slash, later
+        let prefix = prompt_prefix(browser);
+        let prompt_txt = browser.prompt_txt();
+
+        if !prefix.is_empty() {
+            bottom_line.push_str(&prompt_txt.iter().collect::<String>());
+        } else if let Some(message) = &browser.message {
+            bottom_line.push_str(message);
+        } else if matches!(browser.mode, Mode::Bookmarks | Mode::GrepResults) {
+            bottom_line.push_str(&browser.list.title);
         } else {
             bottom_line.push_str(
                 &browser
+                    .tab()
                     .current_path
                     .to_str()
                     .expect("couldn't convert current_path to str"),
             );
+            if let Some(count) = browser.loading() {
+                bottom_line.push_str(&format!("  loading {}…", count));
+            }
         }
 
-        if self.width < special_char as usize + self.add_algnmt as usize {
+        if self.width < prefix.len() + self.add_algnmt as usize {
             println!("width too small to contain special char and alignment");
             util::slp(2);
             browser.exit_cur_dir();
         }
-        let mut width = self.width - (special_char as usize + self.add_algnmt as usize);
+        let mut width = self.width - (prefix.len() + self.add_algnmt as usize);
         let left_border = self.bottom_start;
         if self.bottom_start + width < 1 {
             println!("width too small and start + width < 1");
@@ -101,12 +213,12 @@ impl Canvas {
         let mut len = 0;
 
         // find the right_border
-        for i in self.bottom_start..browser.search_txt.len() + 1 {
+        for i in self.bottom_start..prompt_txt.len() + 1 {
             // the last empty character
-            if i == browser.search_txt.len() {
+            if i == prompt_txt.len() {
                 len += 1;
             } else {
-                len += self.get_utf8_len(browser.search_txt[i]);
+                len += self.get_utf8_len(prompt_txt[i]);
             }
             if len > width {
                 break;
@@ -133,10 +245,10 @@ impl Canvas {
             len = 0;
             // decide the correct bottom_start, from right to left
             loop {
-                if i == browser.search_txt.len() {
+                if i == prompt_txt.len() {
                     len += 1;
                 } else {
-                    len += self.get_utf8_len(browser.search_txt[i]);
+                    len += self.get_utf8_len(prompt_txt[i]);
                 }
                 if len > width {
                     break;
@@ -176,13 +288,7 @@ impl Canvas {
         if self.add_algnmt {
             result.insert(0, '>');
         }
-        if special_char {
-            match browser.mode {
-                Mode::Search => result.insert(0, '/'),
-                Mode::RevSearch => result.insert(0, '?'),
-                _ => {}
-            }
-        }
+        result.insert_str(0, prefix);
         result
     }
 
@@ -243,18 +349,32 @@ impl Canvas {
         let content = self.bottom_line_configure(browser);
         str_to_draw.push_str(&content);
 
-        if matches!(browser.mode, Mode::Search) || matches!(browser.mode, Mode::RevSearch) {
+        // matched/total on the right while filtering or finding
+        if let Some(counts) = browser.counts() {
+            let counts = format!(" {} ", counts);
+            if counts.len() < self.width {
+                str_to_draw.push_str(&csi(&format!(
+                    "{};{}H",
+                    self.height,
+                    self.width - counts.len() + 1
+                )));
+                str_to_draw.push_str(&counts);
+            }
+        }
+
+        let prefix = prompt_prefix(browser);
+        if !prefix.is_empty() {
             // show the cursor when searching
             str_to_draw.push_str(&csi("?25h"));
             let mut real_len = 0;
             for i in self.bottom_start..browser.input_cursor_pos {
-                real_len += self.get_utf8_len(browser.search_txt[i]);
+                real_len += self.get_utf8_len(browser.prompt_txt()[i]);
             }
-            // + 1 + 1: one because ansi escape is 1-index, another one because the extra slash
+            // + 1 + prefix: one because ansi escape is 1-index, then the extra slash
             str_to_draw.push_str(&csi(&format!(
                 "{};{}H",
                 self.height,
-                real_len + 1 + 1 + if self.add_algnmt { 1 } else { 0 }
+                real_len + 1 + prefix.len() + if self.add_algnmt { 1 } else { 0 }
             )));
         }
     }
@@ -271,153 +391,327 @@ impl Canvas {
             }
         }
 
-        let mut pixels = vec![vec![' '; self.width]; self.height];
         let mut str_to_draw = String::from("");
 
         str_to_draw.push_str(&csi("1H"));
         str_to_draw.push_str(&csi("?25l")); // hide cursor
 
-        // l_w_l: left window's left
-        let l_w_l: usize = 0;
-        if self.width / 10 * 6 < 1 {
-            println!("width is too small to divide");
-            util::slp(2);
-            browser.exit_cur_dir();
+        let tab_names = browser.tab_names();
+        self.tab_bar = browser::has_tab_bar(tab_names.len(), self.height);
+        if self.tab_bar {
+            self.draw_tab_bar(&mut str_to_draw, &tab_names, browser.tab_index);
         }
-        let l_w_r: usize = (self.width / 10 * 6 - 1) as usize;
-
-        let r_w_l: usize = l_w_r + 1;
-        if self.width < 1 {
-            println!("width too small, unable to proceed drawing");
-            util::slp(1);
-            browser.exit_cur_dir();
+        // the rows below the tab bar, the last one is covered by the bottom line
+        let height = self.height - self.tab_bar as usize;
+        self.dual = browser.pane.map(|other| browser.tab_index < other);
+        let mut pixels = vec![vec![' '; self.width]; height];
+
+        if matches!(
+            browser.mode,
+            Mode::Bookmarks | Mode::Frecency | Mode::Find | Mode::GrepResults
+        ) {
+            self.draw_list(&mut str_to_draw, &browser.list, height);
+            self.draw_bottom_line(&mut str_to_draw, browser);
+            self.output(&str_to_draw, _test_out);
+            return;
         }
-        let r_w_r: usize = self.width - 1;
-        let preview_width: usize = self.width - r_w_l;
 
-        let mut dir_i: usize = browser.window_start;
-        let mut ch_i: usize;
-
-        // left window
-        for i in 0..=self.height - 1 {
-            if dir_i >= browser.content.len() {
-                break;
-            }
-            let c_a = browser.content[dir_i].chars().collect::<Vec<char>>();
-            ch_i = 0;
-            for j in l_w_l..=l_w_r {
-                if ch_i >= c_a.len() {
+        let layout = self.layout();
+        let guides = browser.tab().tree_guides();
+        let other_guides = browser
+            .other_pane()
+            .map(Tab::tree_guides)
+            .unwrap_or_default();
+
+        // fill the pixels of each column
+        for (pane, start, width) in layout.iter() {
+            for i in 0..height {
+                let name = match pane {
+                    Pane::Parent => browser
+                        .tab()
+                        .parent
+                        .get(browser.parent_window_start() + i)
+                        .cloned(),
+                    Pane::Current => listed(browser.tab(), &guides, browser.tab().window_start + i),
+                    // the last row says when there's more than what's read for the preview
+                    Pane::Preview
+                        if browser.tab().preview_truncated
+                            && i + 1 == height.min(browser.tab().preview.len() + 1) =>
+                    {
+                        Some(String::from("… truncated"))
+                    }
+                    Pane::Preview => browser.tab().preview.get(i).cloned(),
+                    Pane::Other => browser
+                        .other_pane()
+                        .and_then(|other| listed(other, &other_guides, other.window_start + i)),
+                };
+                let Some(name) = name else {
                     break;
+                };
+                for (j, c) in name.chars().take(*width).enumerate() {
+                    self.set_pixel(&mut pixels, i, start + j, c);
                 }
-                self.set_pixel(&mut pixels, i, j, c_a[ch_i]);
-                ch_i += 1;
-            }
-            dir_i += 1;
-            if dir_i >= browser.content.len() {
-                break;
             }
         }
 
-        // right preview window
-        dir_i = 0;
-        for i in 0..=self.height - 1 {
-            if dir_i >= browser.preview.len() {
-                break;
-            }
-            let c_a = browser.preview[dir_i].chars().collect::<Vec<char>>();
-            ch_i = 0;
-            for j in r_w_l..=r_w_r {
-                if ch_i >= c_a.len() {
-                    break;
-                }
-                self.set_pixel(&mut pixels, i, j, c_a[ch_i]);
-                ch_i += 1;
-            }
-            dir_i += 1;
-        }
+        // rows of the current column matching the search
+        let search_positions = (0..height)
+            .map(|i| browser.search_positions(i + browser.tab().window_start))
+            .collect::<Vec<Option<Vec<usize>>>>();
 
         // after setting the pixels, format str_to_draw
-        let mut real_len: usize;
-        let mut do_preview: bool;
-        let mut complement: usize;
-        let mut j;
-        for i in 0..self.height {
-            j = 0;
-            real_len = 0;
-            complement = 0;
-            do_preview = false;
-
-            // Iterate the column, j is jumpable so make it a loop instead of a for
-            loop {
-                if j >= self.width {
-                    break;
+        for i in 0..height {
+            for (pane, start, width) in layout.iter() {
+                let cells = &pixels[i][*start..start + width];
+                match pane {
+                    Pane::Parent => {
+                        let parent_start = browser.parent_window_start();
+                        let is_dir =
+                            browser
+                                .tab()
+                                .parent
+                                .get(parent_start + i)
+                                .is_some_and(|name| {
+                                    browser
+                                        .tab()
+                                        .current_path
+                                        .parent()
+                                        .is_some_and(|parent| parent.join(name).is_dir())
+                                });
+                        // nothing is highlighted at / or when the current directory is hidden
+                        let cursor = browser
+                            .tab()
+                            .parent_cursor
+                            .map_or(usize::MAX, |c| c - parent_start);
+                        self.check_insert_highlight(&mut str_to_draw, i, 0, cursor, is_dir);
+                        self.draw_cells(&mut str_to_draw, cells, |_| false);
+                    }
+                    Pane::Current => {
+                        self.check_insert_highlight(
+                            &mut str_to_draw,
+                            i,
+                            0,
+                            browser.tab().cursor - browser.tab().window_start,
+                            is_dir(false, i, browser),
+                        );
+                        if search_positions[i].is_some() {
+                            str_to_draw.push_str(&self.theme.search_match);
+                        }
+                        if is_marked(i, browser) {
+                            str_to_draw.push_str(&self.theme.marked);
+                        }
+                        // the matches are in the name, after the guides of the tree view
+                        let indent = guides
+                            .get(browser.tab().window_start + i)
+                            .map_or(0, |guide| guide.chars().count());
+                        self.draw_cells(&mut str_to_draw, cells, |j| {
+                            j >= indent && is_match(i, j - indent, browser, &search_positions)
+                        });
+                    }
+                    Pane::Other => {
+                        let other = browser.other_pane().expect("Failed to get the other pane");
+                        let is_dir = other
+                            .content
+                            .get(other.window_start + i)
+                            .is_some_and(|name| other.current_path.join(&*name.path).is_dir());
+                        self.check_insert_highlight(&mut str_to_draw, i, 0, usize::MAX, is_dir);
+                        // the cursor without the focus only gets the background
+                        if other.window_start + i == other.cursor {
+                            str_to_draw.push_str(&self.theme.bottom_bar_background);
+                        }
+                        self.draw_cells(&mut str_to_draw, cells, |_| false);
+                    }
+                    Pane::Preview => {
+                        self.check_insert_highlight(
+                            &mut str_to_draw,
+                            i,
+                            *start,
+                            browser.tab().cursor - browser.tab().window_start,
+                            is_dir(true, i, browser),
+                        );
+                        self.draw_cells(&mut str_to_draw, cells, |_| false);
+                    }
                 }
+            }
+        }
 
-                let len = self.get_utf8_len(pixels[i][j]);
+        // Draw bottom line after drawing the directories to prevent overlapping
+        self.draw_bottom_line(&mut str_to_draw, &browser);
 
-                // for a zero-width character such as a combining character, spaces in pixels is
-                // not enough, insert more spaces (complement) for alignment
-                if len == 0 {
-                    real_len += 1;
-                    complement += 1;
-                } else {
-                    real_len += len;
-                }
+        self.output(&str_to_draw, _test_out);
+    }
 
-                let left_win_len = l_w_r + 1;
-                //  If the real_len reaches over the capcity of the left window, discard this
-                //  character and update the preview window.
-                if real_len > left_win_len && !do_preview {
-                    // If the last character of this window is wide and it causes overflow,
-                    // discard it, insert a white space so it aligns.
-                    if j <= l_w_r
-                        && real_len == left_win_len + 1
-                        && self.get_utf8_len(pixels[i][j]) > 1
-                    {
-                        str_to_draw.push(' ');
-                    }
+    /// Whether the parent directory is drawn, see 'columns' in the config file
+    pub fn has_parent_column(&self) -> bool {
+        self.columns.len() == 3
+    }
 
-                    str_to_draw.push_str(&(0..complement).map(|_| ' ').collect::<String>());
+    /// The columns and where they are, their first column and width. The width is divided by
+    /// the ratios of 'columns', when a column would be too narrow it's left out, the parent
+    /// column first, then the preview.
+    fn layout(&self) -> Vec<(Pane, usize, usize)> {
+        // the dual-pane mode splits the width in two, the tab further left in the tab bar is on
+        // the left
+        if let Some(left) = self.dual {
+            let half = self.width / 2;
+            let (first, second) = if left {
+                (Pane::Current, Pane::Other)
+            } else {
+                (Pane::Other, Pane::Current)
+            };
+            return vec![(first, 0, half), (second, half, self.width - half)];
+        }
+        let panes: &[Pane] = match self.columns.len() {
+            3 => &[Pane::Parent, Pane::Current, Pane::Preview],
+            2 => &[Pane::Current, Pane::Preview],
+            _ => &[Pane::Current],
+        };
+        let mut columns = panes
+            .iter()
+            .copied()
+            .zip(self.columns.iter().copied())
+            .collect::<Vec<(Pane, usize)>>();
+        loop {
+            let unit = self.width / columns.iter().map(|(_, ratio)| ratio).sum::<usize>();
+            if columns.len() == 1 || columns.iter().all(|(_, ratio)| unit * ratio >= MIN_WIDTH) {
+                break;
+            }
+            let dropped = if columns[0].0 == Pane::Parent {
+                0
+            } else {
+                columns.len() - 1
+            };
+            columns.remove(dropped);
+        }
+        let unit = self.width / columns.iter().map(|(_, ratio)| ratio).sum::<usize>();
+        let mut start = 0;
+        columns
+            .iter()
+            .enumerate()
+            .map(|(k, (pane, ratio))| {
+                // the last column takes what's left
+                let width = if k + 1 == columns.len() {
+                    self.width - start
+                } else {
+                    unit * ratio
+                };
+                start += width;
+                (*pane, start - width, width)
+            })
+            .collect()
+    }
 
-                    j = r_w_l;
-                    real_len = 0;
-                    complement = 0;
-                    do_preview = true;
+    /// What is drawn at the row and column, the columns are the same as in draw()
+    pub fn hit(&self, row: usize, col: usize) -> Hit {
+        if row + 1 == self.height {
+            return Hit::BottomBar;
+        }
+        if row >= self.height || (self.tab_bar && row == 0) {
+            return Hit::Nothing;
+        }
+        let row = row - self.tab_bar as usize;
+        let pane = self
+            .layout()
+            .into_iter()
+            .find(|(_, start, width)| (*start..start + width).contains(&col));
+        match pane {
+            Some((Pane::Parent, _, _)) => Hit::Parent(row),
+            Some((Pane::Current, _, _)) => Hit::Current(row),
+            Some((Pane::Preview, _, _)) => Hit::Preview(row),
+            Some((Pane::Other, _, _)) => Hit::Other(row),
+            None => Hit::Nothing,
+        }
+    }
 
-                    continue;
+    /// Draw a row of a column. A zero-width character, such as a combining character, is made up
+    /// for with a space at the end, a wide character that doesn't fit is replaced by a space.
+    fn draw_cells(
+        &self,
+        str_to_draw: &mut String,
+        cells: &[char],
+        matched: impl Fn(usize) -> bool,
+    ) {
+        let width = cells.len();
+        let mut real_len = 0;
+        let mut complement = 0;
+        for (j, c) in cells.iter().enumerate() {
+            let len = self.get_utf8_len(*c);
+            if len == 0 {
+                real_len += 1;
+                complement += 1;
+            } else {
+                real_len += len;
+            }
+            if real_len > width {
+                if real_len == width + 1 && len > 1 {
+                    str_to_draw.push(' ');
                 }
+                break;
+            }
+            if matched(j) {
+                str_to_draw.push_str(MATCH_ON);
+                str_to_draw.push(*c);
+                str_to_draw.push_str(MATCH_OFF);
+            } else {
+                str_to_draw.push(*c);
+            }
+        }
+        str_to_draw.push_str(&(0..complement).map(|_| ' ').collect::<String>());
+    }
 
-                if do_preview && real_len > preview_width {
-                    // Same last wide character discard logic as above
-                    if real_len == preview_width + 1 && self.get_utf8_len(pixels[i][j]) > 1 {
-                        str_to_draw.push(' ');
-                    }
+    /// The tabs in the first line, the current one highlighted
+    fn draw_tab_bar(&self, str_to_draw: &mut String, names: &[String], current: usize) {
+        let mut real_len = 0;
+        for (k, name) in names.iter().enumerate() {
+            if k == current {
+                str_to_draw.push_str(&self.theme.highlight);
+                str_to_draw.push_str(&self.theme.highlight_background);
+            } else {
+                str_to_draw.push_str(&self.theme.bottom_bar);
+                str_to_draw.push_str(&self.theme.bottom_bar_background);
+            }
+            for c in format!(" {}:{} ", k + 1, name).chars() {
+                let len = self.get_utf8_len(c);
+                if real_len + len > self.width {
                     break;
                 }
+                real_len += len;
+                str_to_draw.push(c);
+            }
+        }
+        str_to_draw.push_str(&self.theme.normal);
+        str_to_draw.push_str(&self.theme.normal_background);
+        str_to_draw.push_str(&(real_len..self.width).map(|_| ' ').collect::<String>());
+    }
 
-                // Add highlights
-                if j == 0 || j == r_w_l {
-                    // decide if the directory highlight should be added, this applies to both the left
-                    // window and the right preview window
-                    let is_dir = is_dir(do_preview, i, browser);
-                    // checks and inserts for both windows
-                    self.check_insert_highlight(
-                        &mut str_to_draw,
-                        i,
-                        j,
-                        browser.cursor - browser.window_start,
-                        is_dir,
-                    );
+    /// Draw a list over the rows except the bottom line, one item per line
+    fn draw_list(&self, str_to_draw: &mut String, list: &ListView, height: usize) {
+        for i in 0..height.saturating_sub(1) {
+            let index = list.window_start + i;
+            if index == list.cursor {
+                str_to_draw.push_str(&self.theme.highlight);
+                str_to_draw.push_str(&self.theme.highlight_background);
+            } else {
+                str_to_draw.push_str(&self.theme.normal);
+                str_to_draw.push_str(&self.theme.normal_background);
+            }
+            let mut real_len = 0;
+            if index < list.items.len() {
+                for c in list.items[index].chars() {
+                    let len = self.get_utf8_len(c);
+                    if real_len + len > self.width {
+                        break;
+                    }
+                    real_len += len;
+                    str_to_draw.push(c);
                 }
-                str_to_draw.push(pixels[i][j]);
-                j += 1;
-            } // j
-            str_to_draw.push_str(&(0..complement).map(|_| ' ').collect::<String>());
-        } // i
-
-        // Draw bottom line after drawing the directories to prevent overlapping
-        self.draw_bottom_line(&mut str_to_draw, &browser);
+            }
+            str_to_draw.push_str(&(real_len..self.width).map(|_| ' ').collect::<String>());
+        }
+    }
 
+    #[allow(unused_variables)]
+    fn output(&self, str_to_draw: &str, test_out: Option<&mut String>) {
         #[cfg(not(test))]
         {
             print!("{}", str_to_draw);
@@ -425,7 +719,7 @@ impl Canvas {
         }
         #[cfg(test)]
         {
-            *_test_out.expect("failed to unwrap test output") = str_to_draw.clone();
+            *test_out.expect("failed to unwrap test output") = str_to_draw.to_string();
         }
     }
 }
@@ -438,6 +732,9 @@ pub fn new(config_path: Option<&str>) -> Canvas {
         utf8_table: WcLookupTable::new(),
         bottom_start: 0,
         add_algnmt: false,
+        columns: util::get_columns(config_path),
+        tab_bar: false,
+        dual: None,
     }
 }
 
@@ -445,8 +742,10 @@ pub fn new(config_path: Option<&str>) -> Canvas {
 mod test {
     use super::*;
     use crate::ops::Mode;
+    use crate::tab::Entry;
     use crate::util::test::{mktemp_conf, CleanupDir, CleanupFile, Rand};
     use std::fs::{create_dir, File};
+    use std::mem::take;
     use std::path::PathBuf;
 
     #[test]
@@ -610,7 +909,7 @@ mod test {
         let current_path_buf = PathBuf::from(current_path);
         let mut browser = browser::new(".", None, None);
         browser.mode = Mode::Normal;
-        browser.current_path = current_path_buf.clone();
+        browser.tab_mut().current_path = current_path_buf.clone();
         browser.search_txt = Vec::new();
         browser.input_cursor_pos = 0;
         canvas.width = current_path.chars().count();
@@ -634,7 +933,7 @@ mod test {
         str_to_draw = String::new();
         canvas.width -= to_crop;
         browser.mode = Mode::Normal;
-        browser.current_path = current_path_buf.clone();
+        browser.tab_mut().current_path = current_path_buf.clone();
         browser.search_txt = Vec::new();
         browser.input_cursor_pos = 0;
         canvas.draw_bottom_line(&mut str_to_draw, &browser);
@@ -662,7 +961,7 @@ mod test {
         canvas.width = search_txt.len() + 3;
         let cursor_pos = search_txt.len() / 2;
         browser.mode = Mode::Search;
-        browser.current_path = current_path_buf.clone();
+        browser.tab_mut().current_path = current_path_buf.clone();
         browser.search_txt = search_txt.clone();
         browser.input_cursor_pos = cursor_pos;
         canvas.draw_bottom_line(&mut str_to_draw, &browser);
@@ -693,7 +992,7 @@ mod test {
         let cursor_pos = 0;
         canvas.width = search_txt.len() / 2;
         browser.mode = Mode::Search;
-        browser.current_path = current_path_buf.clone();
+        browser.tab_mut().current_path = current_path_buf.clone();
         browser.search_txt = search_txt.clone();
         browser.input_cursor_pos = cursor_pos;
         canvas.draw_bottom_line(&mut str_to_draw, &browser);
@@ -726,7 +1025,7 @@ mod test {
         let cursor_pos = search_txt.len() - 2; // on the 'a'
         canvas.width = search_txt.len() / 2;
         browser.mode = Mode::Search;
-        browser.current_path = current_path_buf.clone();
+        browser.tab_mut().current_path = current_path_buf.clone();
         browser.search_txt = search_txt.clone();
         browser.input_cursor_pos = cursor_pos;
         canvas.draw_bottom_line(&mut str_to_draw, &browser);
@@ -754,7 +1053,7 @@ mod test {
         let cursor_pos = search_txt.len();
         canvas.width = 18;
         browser.mode = Mode::Search;
-        browser.current_path = current_path_buf.clone();
+        browser.tab_mut().current_path = current_path_buf.clone();
         browser.search_txt = search_txt.clone();
         browser.input_cursor_pos = cursor_pos;
         canvas.draw_bottom_line(&mut str_to_draw, &browser);
@@ -782,7 +1081,7 @@ mod test {
         let cursor_pos = search_txt.len();
         canvas.width = 19;
         browser.mode = Mode::Search;
-        browser.current_path = current_path_buf.clone();
+        browser.tab_mut().current_path = current_path_buf.clone();
         browser.search_txt = search_txt.clone();
         browser.input_cursor_pos = cursor_pos;
         canvas.draw_bottom_line(&mut str_to_draw, &browser);
@@ -810,7 +1109,7 @@ mod test {
         let cursor_pos = search_txt.len();
         canvas.width = 18;
         browser.mode = Mode::RevSearch;
-        browser.current_path = current_path_buf.clone();
+        browser.tab_mut().current_path = current_path_buf.clone();
         browser.search_txt = search_txt.clone();
         browser.input_cursor_pos = cursor_pos;
         canvas.draw_bottom_line(&mut str_to_draw, &browser);
@@ -904,12 +1203,13 @@ mod test {
         assert_eq!(canvas.width, width);
         assert_eq!(canvas.height, height);
         let mut browser = browser::new(&parent, None, None);
+        browser.wait_loaded();
         let current_path = PathBuf::from(parent);
-        browser.cursor = 0;
-        browser.content = content.clone();
-        browser.preview = preview;
-        browser.window_start = 0;
-        browser.current_path = current_path.clone();
+        browser.tab_mut().cursor = 0;
+        browser.tab_mut().content = content.iter().map(|name| name.as_str().into()).collect();
+        browser.tab_mut().preview = preview;
+        browser.tab_mut().window_start = 0;
+        browser.tab_mut().current_path = current_path.clone();
         browser.mode = Mode::Normal;
         browser.search_txt = Vec::new();
         browser.input_cursor_pos = 0;
@@ -927,11 +1227,11 @@ mod test {
             }
             pos += 1;
         }
-        browser.cursor = pos;
-        browser.content = content.clone();
-        browser.preview = Vec::new();
-        browser.window_start = 0;
-        browser.current_path = current_path.clone();
+        browser.tab_mut().cursor = pos;
+        browser.tab_mut().content = content.iter().map(|name| name.as_str().into()).collect();
+        browser.tab_mut().preview = Vec::new();
+        browser.tab_mut().window_start = 0;
+        browser.tab_mut().current_path = current_path.clone();
         browser.mode = Mode::Search;
         browser.search_txt = comp_dir.chars().collect::<Vec<char>>();
         browser.input_cursor_pos = 0;
@@ -944,11 +1244,11 @@ mod test {
         // maximum 18 characters in the left window
         let utf8_filename = ":::冬川や家鴨四五羽に足らぬ水:::";
         content.push(utf8_filename.to_owned());
-        browser.cursor = pos;
-        browser.content = content.clone();
-        browser.preview = Vec::new();
-        browser.window_start = 0;
-        browser.current_path = current_path.clone();
+        browser.tab_mut().cursor = pos;
+        browser.tab_mut().content = content.iter().map(|name| name.as_str().into()).collect();
+        browser.tab_mut().preview = Vec::new();
+        browser.tab_mut().window_start = 0;
+        browser.tab_mut().current_path = current_path.clone();
         browser.mode = Mode::Normal;
         browser.search_txt = Vec::new();
         browser.input_cursor_pos = 0;
@@ -956,6 +1256,273 @@ mod test {
         assert_eq!(test_out, "\u{1b}[1H\u{1b}[?25l\u{1b}[38;5;188m\u{1b}[48;5;236m\u{1b}[38;5;117md1                \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m\u{1b}[38;5;117md2                \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m\u{1b}[38;5;117md3                \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m\u{1b}[38;5;117md4                \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;187m\u{1b}[48;5;238m\u{1b}[38;5;117mzComplicatedDirect\u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236mf1                \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236mf2                \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236mf3                \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m:::冬川や家鴨四五 \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m                  \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m                  \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m                  \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m                  \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[38;5;188m\u{1b}[48;5;236m                  \u{1b}[38;5;188m\u{1b}[48;5;236m            \u{1b}[14H\u{1b}[0K\u{1b}[38;5;188m\u{1b}[48;5;238m                              \u{1b}[14H\u{1b}[0K/tmp/ts-test-draw");
     }
 
+    #[test]
+    fn test_hit() {
+        let canvas = new_canvas(20, 10, None);
+        assert_eq!(canvas.hit(0, 0), Hit::Current(0));
+        assert_eq!(canvas.hit(3, 11), Hit::Current(3));
+        assert_eq!(canvas.hit(3, 12), Hit::Preview(3));
+        assert_eq!(canvas.hit(8, 19), Hit::Preview(8));
+        assert_eq!(canvas.hit(9, 5), Hit::BottomBar);
+        assert_eq!(canvas.hit(10, 5), Hit::Nothing);
+        assert_eq!(canvas.hit(2, 20), Hit::Nothing);
+    }
+
+    #[test]
+    fn test_draw_tab_bar() {
+        let dir = "/tmp/ts-test-draw-tab-bar";
+        let _cd = CleanupDir {
+            dir: dir.to_owned(),
+        };
+        let _ = create_dir(dir);
+        let mut canvas = new_canvas(40, 4, None);
+        let mut browser = browser::new(dir, None, None);
+        let mut test_out = String::new();
+        canvas.draw(&browser, Some(&mut test_out));
+        assert!(!canvas.tab_bar);
+
+        browser.tabs.push(Tab::default());
+        canvas.draw(&browser, Some(&mut test_out));
+        let theme = &canvas.theme;
+        assert!(test_out.starts_with(&format!(
+            "\x1b[1H\x1b[?25l{}{} 1:ts-test-draw-tab-bar {}{} 2:/ {}{}           ",
+            theme.highlight,
+            theme.highlight_background,
+            theme.bottom_bar,
+            theme.bottom_bar_background,
+            theme.normal,
+            theme.normal_background,
+        )));
+        // the rows below the tab bar are one lower for the mouse
+        assert_eq!(canvas.hit(0, 0), Hit::Nothing);
+        assert_eq!(canvas.hit(1, 0), Hit::Current(0));
+        assert_eq!(canvas.hit(3, 0), Hit::BottomBar);
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut canvas = new_canvas(80, 10, None);
+        assert_eq!(
+            canvas.layout(),
+            [(Pane::Current, 0, 48), (Pane::Preview, 48, 32)]
+        );
+        canvas.columns = vec![1, 3, 4];
+        assert_eq!(
+            canvas.layout(),
+            [
+                (Pane::Parent, 0, 10),
+                (Pane::Current, 10, 30),
+                (Pane::Preview, 40, 40)
+            ]
+        );
+        assert_eq!(canvas.hit(2, 9), Hit::Parent(2));
+        assert_eq!(canvas.hit(2, 10), Hit::Current(2));
+        // too narrow for the parent column, then for the preview
+        canvas.width = 30;
+        assert_eq!(
+            canvas.layout(),
+            [(Pane::Current, 0, 12), (Pane::Preview, 12, 18)]
+        );
+        canvas.width = 10;
+        assert_eq!(canvas.layout(), [(Pane::Current, 0, 10)]);
+        canvas.columns = vec![1];
+        canvas.width = 80;
+        assert_eq!(canvas.layout(), [(Pane::Current, 0, 80)]);
+        // the dual-pane mode doesn't care about 'columns'
+        canvas.dual = Some(false);
+        canvas.width = 81;
+        assert_eq!(
+            canvas.layout(),
+            [(Pane::Other, 0, 40), (Pane::Current, 40, 41)]
+        );
+        assert_eq!(canvas.hit(3, 39), Hit::Other(3));
+    }
+
+    #[test]
+    fn test_draw_tree() {
+        let mut canvas = new_canvas(16, 5, None);
+        let mut browser = browser::new(".", None, None);
+        browser.tab_mut().tree = true;
+        browser.tab_mut().content = vec![
+            Entry {
+                path: "a".into(),
+                depth: 0,
+            },
+            Entry {
+                path: "a/xy".into(),
+                depth: 1,
+            },
+            Entry {
+                path: "a/z".into(),
+                depth: 1,
+            },
+        ];
+        browser.search_txt = vec!['y'];
+        browser.compile_search();
+        let mut test_out = String::new();
+        canvas.draw(&browser, Some(&mut test_out));
+        // the guides are drawn before the names, the match is in the name
+        assert!(test_out.contains(&format!("├─ x{}y{}", MATCH_ON, MATCH_OFF)));
+        assert!(test_out.contains("└─ z"));
+    }
+
+    #[test]
+    fn test_draw_truncated_preview() {
+        let mut canvas = new_canvas(20, 5, None);
+        let mut browser = browser::new(".", None, None);
+        browser.tab_mut().content = vec!["a".into()];
+        browser.tab_mut().preview = (0..10).map(|i| format!("p{}", i)).collect();
+        let mut test_out = String::new();
+        canvas.draw(&browser, Some(&mut test_out));
+        assert!(test_out.contains("p4") && !test_out.contains("truncated"));
+        // the last row says there's more
+        browser.tab_mut().preview_truncated = true;
+        canvas.draw(&browser, Some(&mut test_out));
+        assert!(test_out.contains("p3") && !test_out.contains("p4"));
+        assert!(test_out.contains("… trunc"));
+    }
+
+    #[test]
+    fn test_draw_dual() {
+        let dir = "/tmp/ts-test-draw-dual";
+        let _cd = CleanupDir {
+            dir: dir.to_owned(),
+        };
+        let _ = create_dir(dir);
+        for name in ["a", "b"] {
+            let _ = create_dir(format!("{}/{}", dir, name));
+        }
+        let mut canvas = new_canvas(16, 4, None);
+        let mut browser = browser::new(dir, None, None);
+        browser.wait_loaded();
+        let mut other = browser::new(&format!("{}/a", dir), None, None);
+        other.wait_loaded();
+        other.tab_mut().content = vec!["x".into(), "y".into()];
+        other.tab_mut().cursor = 1;
+        browser.tabs.push(take(other.tab_mut()));
+        browser.pane = Some(1);
+        let mut test_out = String::new();
+        canvas.draw(&browser, Some(&mut test_out));
+        let theme = &canvas.theme;
+        // the tab bar, then the two panes side by side, the other pane's cursor only has the
+        // background of the bottom bar
+        assert!(test_out.contains(&format!(
+            "{}{}{}a       {}{}x       {}{}{}b       {}{}{}y       ",
+            theme.highlight,
+            theme.highlight_background,
+            theme.highlight_dir,
+            theme.normal,
+            theme.normal_background,
+            theme.normal,
+            theme.normal_background,
+            theme.highlight_dir,
+            theme.normal,
+            theme.normal_background,
+            theme.bottom_bar_background,
+        )));
+    }
+
+    #[test]
+    fn test_draw_parent() {
+        let parent = "/tmp/ts-test-draw-parent";
+        let _cd = CleanupDir {
+            dir: parent.to_owned(),
+        };
+        let _ = create_dir(parent);
+        for dir in ["a", "b", "b/c"] {
+            let _ = create_dir(format!("{}/{}", parent, dir));
+        }
+        let mut canvas = new_canvas(24, 4, None);
+        canvas.columns = vec![1, 1, 1];
+        let mut browser = browser::new(&format!("{}/b", parent), None, None);
+        browser.wait_loaded();
+        browser.tab_mut().parent = vec!["a".to_string(), "b".to_string()];
+        browser.tab_mut().parent_cursor = Some(1);
+        let mut test_out = String::new();
+        canvas.draw(&browser, Some(&mut test_out));
+        // the current directory is highlighted in the parent column
+        let theme = &canvas.theme;
+        assert!(test_out.starts_with(&format!(
+            "\x1b[1H\x1b[?25l{}{}{}a       {}{}{}c       ",
+            theme.normal,
+            theme.normal_background,
+            theme.highlight_dir,
+            theme.highlight,
+            theme.highlight_background,
+            theme.highlight_dir,
+        )));
+        assert!(test_out.contains(&format!(
+            "{}{}{}b       ",
+            theme.highlight, theme.highlight_background, theme.highlight_dir
+        )));
+    }
+
+    #[test]
+    fn test_draw_list() {
+        let mut canvas = new_canvas(8, 4, None);
+        let mut browser = browser::new(".", None, None);
+        browser.mode = Mode::Bookmarks;
+        browser.list = ListView::new(
+            "title",
+            vec!["a  /tmp".to_string(), "b  /usr/lib".to_string()],
+        );
+        browser.list.cursor = 1;
+        let mut test_out = String::new();
+        canvas.draw(&browser, Some(&mut test_out));
+        let (h, hb, n, nb) = (
+            &canvas.theme.highlight,
+            &canvas.theme.highlight_background,
+            &canvas.theme.normal,
+            &canvas.theme.normal_background,
+        );
+        assert_eq!(
+            test_out,
+            format!(
+                "\u{1b}[1H\u{1b}[?25l{n}{nb}a  /tmp {h}{hb}b  /usr/{n}{nb}        \u{1b}[4H\u{1b}[0K{}{}        \u{1b}[4H\u{1b}[0Ktitle",
+                canvas.theme.bottom_bar, canvas.theme.bottom_bar_background
+            )
+        );
+    }
+
+    #[test]
+    fn test_draw_filter() {
+        let mut canvas = new_canvas(20, 4, None);
+        let mut browser = browser::new(".", None, None);
+        browser.tab_mut().content = vec!["foo_bar".into()];
+        browser.tab_mut().filter = Some(browser::Filter {
+            fuzzy: true,
+            all: vec!["foo_bar".into(), "a".into(), "b".into()],
+            positions: vec![vec![0, 4]],
+            scores: vec![0],
+        });
+        let mut test_out = String::new();
+        canvas.draw(&browser, Some(&mut test_out));
+        // matched characters are emphasized, the counts are on the right of the bottom bar
+        assert!(test_out.contains(&format!(
+            "{MATCH_ON}f{MATCH_OFF}oo_{MATCH_ON}b{MATCH_OFF}ar"
+        )));
+        assert!(test_out.ends_with("\u{1b}[4;16H 1/3 "));
+    }
+
+    #[test]
+    fn test_draw_search() {
+        let mut canvas = new_canvas(20, 4, None);
+        let mut browser = browser::new(".", None, None);
+        browser.tab_mut().content = vec!["foo".into(), "bar".into(), "baz".into()];
+        browser.search_txt = vec!['b', 'a'];
+        browser.compile_search();
+        browser.tab_mut().cursor = 1;
+        let mut test_out = String::new();
+        canvas.draw(&browser, Some(&mut test_out));
+        // matching rows are drawn in the search match color, the matched text is emphasized
+        assert!(test_out.contains(&format!(
+            "{}{MATCH_ON}b{MATCH_OFF}{MATCH_ON}a{MATCH_OFF}z",
+            canvas.theme.search_match
+        )));
+        assert!(!test_out.contains(&format!("{}foo", canvas.theme.search_match)));
+        assert!(test_out.ends_with("\u{1b}[4;14H [1/2] "));
+    }
+
     #[test]
     fn test_draw_empty_dir() {
         let width = 31;
@@ -976,8 +1543,9 @@ mod test {
 
         // don't pass parent directly to browser to avoid /private/tmp on MacOS
         let mut browser = browser::new(".", None, Some(&conf));
-        browser.content = Vec::new();
-        browser.current_path = PathBuf::from(parent);
+        browser.wait_loaded();
+        browser.tab_mut().content = Vec::new();
+        browser.tab_mut().current_path = PathBuf::from(parent);
         let mut test_out = String::new();
         // everything is empty (in an empty directory)
         canvas.draw(&browser, Some(&mut test_out));
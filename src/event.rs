@@ -0,0 +1,179 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use libc::{
+    c_int, c_void, pipe2, poll, pollfd, sigaction, sigemptyset, write, O_CLOEXEC, O_NONBLOCK,
+    POLLIN, SA_RESTART, SIGWINCH, SIG_DFL, STDIN_FILENO,
+};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+// the write end of the signal self-pipe, for the signal handler
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(_: c_int) {
+    let fd = SIGNAL_PIPE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        unsafe { write(fd, [0_u8].as_ptr() as *const c_void, 1) };
+    }
+}
+
+/// A non-blocking pipe, the read end and the write end
+fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { pipe2(fds.as_mut_ptr(), O_NONBLOCK | O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+/// Read everything in a non-blocking pipe, what's written to it only matters as a wake up
+fn drain(pipe: &mut File) {
+    let mut buf = [0_u8; 64];
+    while matches!(pipe.read(&mut buf), Ok(len) if len > 0) {}
+}
+
+fn set_handler(signal: c_int, handler: usize) {
+    unsafe {
+        let mut action: sigaction = mem::zeroed();
+        action.sa_sigaction = handler;
+        // a read of the keys isn't interrupted
+        action.sa_flags = SA_RESTART;
+        sigemptyset(&mut action.sa_mask);
+        sigaction(signal, &action, ptr::null_mut());
+    }
+}
+
+/// Wakes the event loop up from another thread, when a job is done
+#[derive(Clone)]
+pub struct Waker(Arc<File>);
+
+impl Waker {
+    pub fn wake(&self) {
+        // a full pipe wakes the loop up just as well
+        let _ = (&*self.0).write(&[0]);
+    }
+}
+
+/// What's ready after a wait, more than one thing can be
+#[derive(Default, PartialEq, Debug)]
+pub struct Ready {
+    pub key: bool,
+    // the terminal was resized
+    pub resize: bool,
+    // a watched directory changed
+    pub watch: bool,
+    // a job is done
+    pub work: bool,
+}
+
+/// The event loop's sources besides the keys: signals through a self-pipe, the inotify watches and
+/// the jobs on other threads
+pub struct Events {
+    signals: File,
+    // the signal handler writes to it by its fd
+    _signals_tx: File,
+    wake: File,
+    waker: Waker,
+}
+
+impl Events {
+    /// Set up the pipes and the handler of SIGWINCH
+    pub fn new() -> io::Result<Events> {
+        let (signals, signals_tx) = pipe()?;
+        let (wake, wake_tx) = pipe()?;
+        SIGNAL_PIPE.store(signals_tx.as_raw_fd(), Ordering::Relaxed);
+        set_handler(SIGWINCH, on_signal as *const () as usize);
+        Ok(Events {
+            signals,
+            _signals_tx: signals_tx,
+            wake,
+            waker: Waker(Arc::new(wake_tx)),
+        })
+    }
+
+    pub fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    /// Wait for at most timeout milliseconds, -1 waits forever, for a key, a signal, a change in
+    /// the watched directories (watch is the inotify fd) or a job to be done
+    pub fn wait(&mut self, watch: Option<i32>, timeout: i32) -> Ready {
+        let fds = [
+            STDIN_FILENO,
+            self.signals.as_raw_fd(),
+            self.wake.as_raw_fd(),
+            // a negative fd is left out by poll
+            watch.unwrap_or(-1),
+        ];
+        let mut fds = fds.map(|fd| pollfd {
+            fd,
+            events: POLLIN,
+            revents: 0,
+        });
+        // interrupted by a signal, the self-pipe has it for the next wait
+        if unsafe { poll(fds.as_mut_ptr(), fds.len() as _, timeout) } <= 0 {
+            return Ready::default();
+        }
+        let ready = Ready {
+            key: fds[0].revents != 0,
+            resize: fds[1].revents != 0,
+            work: fds[2].revents != 0,
+            watch: fds[3].revents != 0,
+        };
+        // only what's been seen, what comes after the poll is for the next wait
+        if ready.resize {
+            drain(&mut self.signals);
+        }
+        if ready.work {
+            drain(&mut self.wake);
+        }
+        ready
+    }
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        set_handler(SIGWINCH, SIG_DFL);
+        SIGNAL_PIPE.store(-1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_wait() {
+        let mut events = Events::new().expect("failed to set up events");
+        // stdin of the tests may always be ready, wait until the event is there too
+        let wait_for = |events: &mut Events, ready: fn(&Ready) -> bool| {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_secs(5) {
+                if ready(&events.wait(None, 50)) {
+                    return true;
+                }
+            }
+            false
+        };
+        let waker = events.waker();
+        thread::spawn(move || waker.wake());
+        assert!(wait_for(&mut events, |ready| ready.work));
+        unsafe { libc::raise(SIGWINCH) };
+        assert!(wait_for(&mut events, |ready| ready.resize));
+        // drained
+        assert!(!events.wait(None, 0).work);
+    }
+}
@@ -0,0 +1,94 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::event::Waker;
+use crate::walk;
+use crate::work::Job;
+use std::path::PathBuf;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+// paths are sent in batches, one message per path is too slow for big trees
+const BATCH: usize = 256;
+
+/// Everything under a directory, found by a background thread. The paths are relative to the
+/// directory and arrive bit by bit, each batch wakes up the event loop.
+pub struct Finder {
+    pub root: PathBuf,
+    pub found: Vec<String>,
+    // the thread is still walking
+    pub running: bool,
+    job: Job<Vec<String>>,
+}
+
+impl Finder {
+    pub fn start(root: PathBuf, waker: Option<Waker>) -> Finder {
+        let walk_root = root.clone();
+        // a dropped finder cancels the walk
+        let job = Job::stream(waker, move |cancel, stream| {
+            let mut batch = Vec::new();
+            walk::walk(&walk_root, cancel, &mut |path, _| {
+                if let Ok(relative) = path.strip_prefix(&walk_root) {
+                    batch.push(relative.to_string_lossy().to_string());
+                }
+                if batch.len() >= BATCH {
+                    stream.send(std::mem::take(&mut batch));
+                }
+            });
+            stream.send(batch);
+        });
+        Finder {
+            root,
+            found: Vec::new(),
+            running: true,
+            job,
+        }
+    }
+
+    /// Take what the thread has found so far without waiting
+    ///
+    /// returns
+    ///  whether anything new arrived
+    pub fn poll(&mut self) -> bool {
+        let mut new = false;
+        loop {
+            match self.job.next(Duration::ZERO) {
+                Ok(batch) => {
+                    new |= !batch.is_empty();
+                    self.found.extend(batch);
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    new |= self.running;
+                    self.running = false;
+                    break;
+                }
+            }
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::random_dir_wcontent;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_finder() {
+        let (files, dirs, root_dir, _cd) = random_dir_wcontent();
+        let mut finder = Finder::start(PathBuf::from(format!("/tmp/{}", root_dir)), None);
+        while finder.running {
+            finder.poll();
+        }
+        let found = finder.found.iter().cloned().collect::<HashSet<String>>();
+        let expected = files.into_iter().chain(dirs).collect::<HashSet<String>>();
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,243 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::matcher;
+use crate::util;
+use std::fs::read_to_string;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// same as z.sh, ranks are aged once they add up to this
+const MAX_RANK: f64 = 9000.0;
+const AGING: f64 = 0.99;
+
+const HOUR: u64 = 3600;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+struct Dir {
+    path: PathBuf,
+    rank: f64,
+    // last visit, seconds since epoch
+    time: u64,
+}
+
+/// Visited directories ranked by frequency and recency, like z and zoxide. Saved as
+/// "<path>|<rank>|<time>" lines, the same as z.sh, with the path escaped with util::escape so a
+/// newline in it doesn't split a line.
+pub struct Frecency {
+    file: Option<PathBuf>,
+    dirs: Vec<Dir>,
+    // directories visited in this session, added to the file when saving
+    visits: Vec<(PathBuf, u64)>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_dirs(file: &Option<PathBuf>) -> Vec<Dir> {
+    let content = match file {
+        Some(file) => read_to_string(file).unwrap_or_default(),
+        None => return Vec::new(),
+    };
+    let mut dirs = Vec::new();
+    for line in content.lines() {
+        // the path may contain '|' as well
+        let mut fields = line.rsplitn(3, '|');
+        let (Some(time), Some(rank), Some(path)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if let (Ok(rank), Ok(time)) = (rank.parse::<f64>(), time.parse::<u64>()) {
+            dirs.push(Dir {
+                path: PathBuf::from(util::unescape(path)),
+                rank,
+                time,
+            });
+        }
+    }
+    dirs
+}
+
+fn add(dirs: &mut Vec<Dir>, path: &Path, time: u64) {
+    match dirs.iter_mut().find(|dir| dir.path == path) {
+        Some(dir) => {
+            dir.rank += 1.0;
+            dir.time = time;
+        }
+        None => dirs.push(Dir {
+            path: path.to_path_buf(),
+            rank: 1.0,
+            time,
+        }),
+    }
+}
+
+impl Dir {
+    /// Rank weighted by how long ago the directory was visited
+    fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.time);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.rank * weight
+    }
+}
+
+impl Frecency {
+    /// Read the directories from file, the ones that are gone aren't offered anymore
+    pub fn load(file: Option<PathBuf>) -> Frecency {
+        let mut dirs = read_dirs(&file);
+        dirs.retain(|dir| dir.path.is_dir());
+        Frecency {
+            dirs,
+            file,
+            visits: Vec::new(),
+        }
+    }
+
+    /// A directory is visited
+    pub fn add(&mut self, path: &Path) {
+        let time = now();
+        add(&mut self.dirs, path, time);
+        self.visits.push((path.to_path_buf(), time));
+    }
+
+    /// Add the visits of this session to what is in the file now, other trans instances may have
+    /// saved theirs in the meantime. cd is where trans exits to if it wasn't visited with add.
+    /// Old ranks are aged and gone directories are dropped. The file is locked until it's
+    /// written, so the visits of another one saving meanwhile aren't lost.
+    pub fn save(&self, cd: Option<&Path>) -> io::Result<()> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let _lock = util::lock(file)?;
+        let mut dirs = read_dirs(&self.file);
+        for (path, time) in self.visits.iter() {
+            add(&mut dirs, path, *time);
+        }
+        if let Some(path) = cd {
+            add(&mut dirs, path, now());
+        }
+
+        if dirs.iter().map(|dir| dir.rank).sum::<f64>() > MAX_RANK {
+            for dir in dirs.iter_mut() {
+                dir.rank *= AGING;
+            }
+            dirs.retain(|dir| dir.rank >= 1.0);
+        }
+        dirs.retain(|dir| dir.path.is_dir());
+
+        let mut content = String::new();
+        for dir in dirs.iter() {
+            content.push_str(&format!(
+                "{}|{}|{}\n",
+                util::escape(&dir.path.to_string_lossy()),
+                dir.rank,
+                dir.time
+            ));
+        }
+        util::write_atomic(file, content.as_bytes())
+    }
+
+    /// Directories that fuzzy match the query, the best first. How well it matches counts more
+    /// than frecency, and matching the last component counts more than matching the rest.
+    pub fn query(&self, query: &[char]) -> Vec<PathBuf> {
+        let now = now();
+        let mut ranked = Vec::new();
+        for dir in self.dirs.iter() {
+            let path = dir.path.to_string_lossy();
+            let Some((mut score, _)) = matcher::fuzzy_match(query, &path) else {
+                continue;
+            };
+            let name = dir.path.file_name().map(|name| name.to_string_lossy());
+            if let Some((name_score, _)) = name.and_then(|n| matcher::fuzzy_match(query, &n)) {
+                score = score.max(name_score) * 2;
+            }
+            let frecency = dir.frecency(now);
+            ranked.push((
+                score as f64 * (1.0 + (1.0 + frecency).ln()),
+                frecency,
+                &dir.path,
+            ));
+        }
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.total_cmp(&a.1)));
+        ranked
+            .into_iter()
+            .map(|(_, _, path)| path.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::random_dir_wcontent;
+    use std::fs::create_dir;
+
+    #[test]
+    fn test_frecency() {
+        let (_, dirs, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        let file = root.join("state").join("frecency");
+        let (a, b) = (root.join(&dirs[0]), root.join(&dirs[1]));
+        let gone = root.join("gone|dir");
+        let time = now();
+        let content = format!(
+            "{}|1|{}\n{}|5|{}\n",
+            gone.to_string_lossy(),
+            time,
+            a.to_string_lossy(),
+            time - WEEK
+        );
+        util::write_atomic(&file, content.as_bytes()).expect("failed to write frecency file");
+
+        let mut frecency = Frecency::load(Some(file.clone()));
+        frecency.add(&b);
+        frecency.add(&b);
+        // b is visited recently, it beats the old a, the gone directory isn't offered
+        assert_eq!(frecency.query(&[]), [b.clone(), a.clone()]);
+        // matching the name is better than matching the rest of the path
+        let name = dirs[0].chars().collect::<Vec<char>>();
+        assert_eq!(frecency.query(&name)[0], a);
+
+        // the visits are added to the file, gone directories are dropped
+        frecency
+            .save(Some(&a))
+            .expect("failed to save frecency file");
+        let saved = read_dirs(&Some(file.clone()));
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[0].path, a);
+        assert_eq!(saved[0].rank, 6.0);
+        assert_eq!(saved[1].path, b);
+        assert_eq!(saved[1].rank, 2.0);
+
+        // a newline in the path doesn't split its line
+        let c = root.join("new\nline|1|2");
+        create_dir(&c).expect("failed to create dir");
+        let mut frecency = Frecency::load(Some(file.clone()));
+        frecency.add(&c);
+        frecency.save(None).expect("failed to save frecency file");
+        let saved = read_dirs(&Some(file));
+        assert_eq!(saved.len(), 3);
+        assert_eq!(saved[2].path, c);
+        assert_eq!(saved[2].rank, 1.0);
+    }
+}
@@ -0,0 +1,203 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::event::Waker;
+use crate::walk;
+use crate::work::Job;
+use regex_lite::Regex;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+// a file with a NUL byte in the first this many bytes is binary, like git and grep do
+const BINARY_CHECK_LEN: usize = 8000;
+// bigger files are skipped, they are rarely what anyone greps for
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+// only this many characters of a matching line are kept
+const MAX_TEXT_LEN: usize = 256;
+// progress is sent at least once every this many files
+const PROGRESS_FILES: usize = 64;
+
+/// A matching line
+pub struct Hit {
+    // relative to the directory being searched
+    pub path: String,
+    // 1-based, like editors count
+    pub line: usize,
+    pub text: String,
+}
+
+/// Files searched so far and the hits found since the last batch
+struct Batch {
+    files: usize,
+    hits: Vec<Hit>,
+}
+
+/// Lines matching a regex in the files under a directory, searched by a background thread. Each
+/// batch wakes up the event loop.
+pub struct Grepper {
+    pub root: PathBuf,
+    pub hits: Vec<Hit>,
+    // files searched so far
+    pub files: usize,
+    // the thread is still searching
+    pub running: bool,
+    job: Job<Batch>,
+}
+
+/// Matching lines of a file, None if it's binary or can't be read
+fn grep_file(path: &Path, re: &Regex) -> Option<Vec<(usize, String)>> {
+    let mut file = File::open(path).ok()?;
+    if file.metadata().ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).ok()?;
+    if content[..content.len().min(BINARY_CHECK_LEN)].contains(&0) {
+        return None;
+    }
+    let content = String::from_utf8_lossy(&content);
+    let hits = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| re.is_match(line))
+        .map(|(i, line)| {
+            // tabs and other control characters would mess up the screen
+            let text = line
+                .chars()
+                .take(MAX_TEXT_LEN)
+                .map(|c| if c.is_control() { ' ' } else { c })
+                .collect();
+            (i + 1, text)
+        })
+        .collect();
+    Some(hits)
+}
+
+impl Hit {
+    /// path:line: text
+    pub fn item(&self) -> String {
+        format!("{}:{}: {}", self.path, self.line, self.text.trim())
+    }
+}
+
+impl Grepper {
+    pub fn start(root: PathBuf, re: Regex, waker: Option<Waker>) -> Grepper {
+        let walk_root = root.clone();
+        // a dropped grepper cancels the walk
+        let job = Job::stream(waker, move |cancel, stream| {
+            let mut batch = Batch {
+                files: 0,
+                hits: Vec::new(),
+            };
+            walk::walk(&walk_root, cancel, &mut |path, is_dir| {
+                if is_dir {
+                    return;
+                }
+                batch.files += 1;
+                let relative = path.strip_prefix(&walk_root).unwrap_or(path);
+                for (line, text) in grep_file(path, &re).unwrap_or_default() {
+                    batch.hits.push(Hit {
+                        path: relative.to_string_lossy().to_string(),
+                        line,
+                        text,
+                    });
+                }
+                // is_multiple_of needs a newer rustc
+                #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+                let progress = batch.files % PROGRESS_FILES == 0;
+                if !batch.hits.is_empty() || progress {
+                    let files = batch.files;
+                    let hits = std::mem::take(&mut batch.hits);
+                    stream.send(Batch { files, hits });
+                }
+            });
+            stream.send(batch);
+        });
+        Grepper {
+            root,
+            hits: Vec::new(),
+            files: 0,
+            running: true,
+            job,
+        }
+    }
+
+    /// Take what the thread has found so far without waiting
+    ///
+    /// returns
+    ///  whether there's any progress
+    pub fn poll(&mut self) -> bool {
+        let mut progress = false;
+        loop {
+            match self.job.next(Duration::ZERO) {
+                Ok(batch) => {
+                    progress = true;
+                    self.files = batch.files;
+                    self.hits.extend(batch.hits);
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    progress |= self.running;
+                    self.running = false;
+                    break;
+                }
+            }
+        }
+        progress
+    }
+
+    /// Stop searching, what's found so far is kept
+    pub fn cancel(&mut self) {
+        self.job.cancel();
+        self.running = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::CleanupDir;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn test_grepper() {
+        let root = PathBuf::from("/tmp/ts-test-grep");
+        let _cd = CleanupDir {
+            dir: root.to_string_lossy().to_string(),
+        };
+        create_dir_all(root.join("src")).unwrap();
+        write(
+            root.join("src/main.rs"),
+            "fn main() {\n\tprintln!(\"trans\");\n}\n",
+        )
+        .unwrap();
+        write(root.join("README"), "Trans rights\n").unwrap();
+        write(root.join("binary"), b"trans\0").unwrap();
+        write(root.join("ignored.txt"), "trans\n").unwrap();
+        write(root.join(".ignore"), "*.txt\n").unwrap();
+
+        let re = Regex::new("(?i)trans").unwrap();
+        let mut grepper = Grepper::start(root.clone(), re, None);
+        while grepper.running {
+            grepper.poll();
+        }
+        let items = grepper.hits.iter().map(Hit::item).collect::<Vec<String>>();
+        assert_eq!(
+            items,
+            [
+                "README:1: Trans rights",
+                "src/main.rs:2: println!(\"trans\");"
+            ]
+        );
+        // .ignore, binary, README, src/main.rs
+        assert_eq!(grepper.files, 4);
+    }
+}
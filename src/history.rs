@@ -0,0 +1,182 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::util;
+use std::collections::VecDeque;
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+
+const HISTORY_LEN: usize = 256;
+
+/// Searches of this and earlier sessions, one per line, the latest last. Up and down browse
+/// them like a shell does, only the ones starting with what was typed before browsing.
+pub struct History {
+    file: Option<PathBuf>,
+    entries: VecDeque<String>,
+    // the entry being shown, entries.len() when not browsing
+    index: usize,
+    // typed before browsing
+    prefix: String,
+}
+
+fn read_entries(file: &Option<PathBuf>) -> VecDeque<String> {
+    match file {
+        Some(file) => read_to_string(file)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+        None => VecDeque::new(),
+    }
+}
+
+/// Add an entry as the latest one, an earlier same entry is dropped
+fn push(entries: &mut VecDeque<String>, entry: &str) {
+    entries.retain(|e| e != entry);
+    entries.push_back(entry.to_string());
+    while entries.len() > HISTORY_LEN {
+        entries.pop_front();
+    }
+}
+
+impl History {
+    pub fn load(file: Option<PathBuf>) -> History {
+        let entries = read_entries(&file);
+        History {
+            index: entries.len(),
+            file,
+            entries,
+            prefix: String::new(),
+        }
+    }
+
+    /// A search is done. It's added to what is in the file now, other trans instances may have
+    /// added theirs in the meantime, and those show up here as well. The file is locked until
+    /// it's written, so an entry another one adds meanwhile isn't lost.
+    pub fn add(&mut self, entry: &str) -> io::Result<()> {
+        let _lock = match &self.file {
+            Some(file) if !entry.is_empty() => Some(util::lock(file)?),
+            _ => None,
+        };
+        if !entry.is_empty() {
+            if self.file.is_some() {
+                self.entries = read_entries(&self.file);
+            }
+            push(&mut self.entries, entry);
+        }
+        self.reset();
+        let file = match &self.file {
+            Some(file) if !entry.is_empty() => file,
+            _ => return Ok(()),
+        };
+        let mut content = String::new();
+        for entry in self.entries.iter() {
+            content.push_str(entry);
+            content.push('\n');
+        }
+        util::write_atomic(file, content.as_bytes())
+    }
+
+    /// Stop browsing, the next up starts from the latest entry again
+    pub fn reset(&mut self) {
+        self.index = self.entries.len();
+        self.prefix.clear();
+    }
+
+    /// The entry before the one being shown that starts with the typed text, None if there's no
+    /// such entry
+    pub fn up(&mut self, typed: &str) -> Option<&str> {
+        if self.index >= self.entries.len() {
+            self.prefix = typed.to_string();
+        }
+        let i = (0..self.index)
+            .rev()
+            .find(|i| self.entries[*i].starts_with(&self.prefix) && self.entries[*i] != typed)?;
+        self.index = i;
+        Some(&self.entries[i])
+    }
+
+    /// The entry after the one being shown that starts with the typed text. Past the latest one
+    /// it's what was typed before browsing, None if not browsing.
+    pub fn down(&mut self) -> Option<&str> {
+        if self.index >= self.entries.len() {
+            return None;
+        }
+        match (self.index + 1..self.entries.len())
+            .find(|i| self.entries[*i].starts_with(&self.prefix))
+        {
+            Some(i) => {
+                self.index = i;
+                Some(&self.entries[i])
+            }
+            None => {
+                self.index = self.entries.len();
+                Some(&self.prefix)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::random_dir_wcontent;
+    use std::thread;
+
+    #[test]
+    fn test_history() {
+        let (_, _, root_dir, _cd) = random_dir_wcontent();
+        let file = PathBuf::from(format!("/tmp/{}/state/search_history", root_dir));
+        let mut history = History::load(Some(file.clone()));
+        for entry in ["foo", "bar", "fob", "foo", ""] {
+            history.add(entry).expect("failed to save history");
+        }
+        assert_eq!(history.entries, ["bar", "fob", "foo"]);
+
+        // only the entries starting with what's typed
+        assert_eq!(history.up("fo"), Some("foo"));
+        assert_eq!(history.up("foo"), Some("fob"));
+        assert_eq!(history.up("fob"), None);
+        assert_eq!(history.down(), Some("foo"));
+        assert_eq!(history.down(), Some("fo"));
+        assert_eq!(history.down(), None);
+        assert_eq!(history.up(""), Some("foo"));
+        assert_eq!(history.up("foo"), Some("fob"));
+        assert_eq!(history.up("fob"), Some("bar"));
+
+        // another instance adds to the file, nothing is lost
+        let mut other = History::load(Some(file.clone()));
+        other.add("baz").expect("failed to save history");
+        history.add("qux").expect("failed to save history");
+        assert_eq!(
+            History::load(Some(file.clone())).entries,
+            ["bar", "fob", "foo", "baz", "qux"]
+        );
+
+        // instances adding at the same time wait for each other
+        let threads = (0..8)
+            .map(|i| {
+                let file = file.clone();
+                thread::spawn(move || {
+                    let mut history = History::load(Some(file));
+                    history
+                        .add(&format!("t{}", i))
+                        .expect("failed to save history");
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().expect("failed to add to history");
+        }
+        let entries = History::load(Some(file)).entries;
+        assert_eq!(entries.len(), 13);
+        assert!((0..8).all(|i| entries.contains(&format!("t{}", i))));
+    }
+}
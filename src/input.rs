@@ -0,0 +1,545 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::util;
+use libc::{c_void, read};
+use std::io;
+use std::str::from_utf8;
+use std::time::{Duration, Instant};
+
+// how long to wait for the rest of an escape sequence before taking ESC as the Esc key, in
+// milliseconds, the same as vim's default ttimeoutlen
+const ESC_TIMEOUT: i32 = 100;
+const PASTE_START: &str = "200";
+const PASTE_END: &[u8] = b"\x1b[201~";
+// a second click on the same cell within this is a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Shift, Alt and Ctrl held with a key
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Mods {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+/// A key press, decoded from what the terminal sends
+#[derive(Clone, PartialEq, Debug)]
+pub enum Key {
+    // a printable character, Shift is already applied
+    Char(char),
+    // Ctrl + <letter>, lowercase. Ctrl + I and Ctrl + M are Tab and Enter
+    Ctrl(char),
+    // Alt + <printable character>
+    Alt(char),
+    Enter,
+    Tab,
+    BackTab,
+    Backspace,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    F(u8),
+    // one of the keys above with modifiers, e.g. Ctrl + Left
+    Modified(Box<Key>, Mods),
+    // bracketed paste
+    Paste(Vec<char>),
+    Mouse(Mouse),
+    // stdin is closed or can't be read, there won't be any more keys
+    Closed,
+    Unknown,
+}
+
+/// What the mouse did
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MouseKind {
+    // left button press
+    Click,
+    DoubleClick,
+    WheelUp,
+    WheelDown,
+    // other buttons, releases and drags
+    Other,
+}
+
+/// A mouse event of SGR mouse reporting, the row and column start from 0
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mouse {
+    pub kind: MouseKind,
+    pub row: usize,
+    pub col: usize,
+}
+
+/// Reads keys from stdin, one at a time, unbuffered so poll() on stdin tells whether a key is
+/// coming
+#[derive(Default)]
+pub struct KeyReader {
+    // stdin, 0, unless it's a test
+    fd: i32,
+    // read but not decoded yet
+    buf: Vec<u8>,
+    // when and where the last click was, to tell a double click
+    last_click: Option<(Instant, usize, usize)>,
+}
+
+/// The modifier parameter of a CSI sequence, 1 + the bits of Shift (1), Alt (2) and Ctrl (4).
+/// The kitty protocol may add the event type after a ':'.
+fn parse_mods(param: &str) -> Mods {
+    let bits = param
+        .split(':')
+        .next()
+        .and_then(|m| m.parse::<u32>().ok())
+        .unwrap_or(1)
+        .saturating_sub(1);
+    Mods {
+        shift: bits & 1 != 0,
+        alt: bits & 2 != 0,
+        ctrl: bits & 4 != 0,
+    }
+}
+
+/// The key with the modifiers, Ctrl and Alt of a character are folded into Key::Ctrl and Key::Alt
+fn with_mods(key: Key, mods: Mods) -> Key {
+    if mods == Mods::default() {
+        return key;
+    }
+    match key {
+        Key::Char(c) if mods.ctrl && !mods.alt => Key::Ctrl(c.to_ascii_lowercase()),
+        Key::Char(c) if mods.alt && !mods.ctrl && mods.shift => Key::Alt(c.to_ascii_uppercase()),
+        Key::Char(c) if mods.alt && !mods.ctrl => Key::Alt(c),
+        Key::Char(c) if !mods.alt && !mods.ctrl => Key::Char(c.to_ascii_uppercase()),
+        key => Key::Modified(Box::new(key), mods),
+    }
+}
+
+/// ESC followed by a key is that key with Alt
+fn with_alt(key: Key) -> Key {
+    match key {
+        Key::Char(c) => Key::Alt(c),
+        key => Key::Modified(
+            Box::new(key),
+            Mods {
+                alt: true,
+                ..Mods::default()
+            },
+        ),
+    }
+}
+
+/// The key of the kitty keyboard protocol, CSI <unicode> ; <mods> u
+fn kitty_key(code: u32, mods: Mods) -> Key {
+    let key = match code {
+        27 => Key::Esc,
+        13 => Key::Enter,
+        9 => Key::Tab,
+        127 => Key::Backspace,
+        code => match char::from_u32(code) {
+            Some(c) if !c.is_control() => Key::Char(c),
+            _ => Key::Unknown,
+        },
+    };
+    with_mods(key, mods)
+}
+
+/// The key of a CSI sequence, ESC [ <params> <final>, e.g. ESC [ 1 ; 5 D is Ctrl + Left
+fn csi_key(params: &str, last: u8) -> Key {
+    let mut params = params.split(';');
+    let number = params
+        .next()
+        .and_then(|n| n.split(':').next())
+        .and_then(|n| n.parse::<u32>().ok())
+        .unwrap_or(1);
+    let mods = params.next().map(parse_mods).unwrap_or_default();
+    let key = match (last, number) {
+        (b'A', _) => Key::Up,
+        (b'B', _) => Key::Down,
+        (b'C', _) => Key::Right,
+        (b'D', _) => Key::Left,
+        (b'H', _) | (b'~', 1 | 7) => Key::Home,
+        (b'F', _) | (b'~', 4 | 8) => Key::End,
+        (b'Z', _) => return Key::BackTab,
+        (b'P'..=b'S', _) => Key::F(last - b'P' + 1),
+        (b'~', 2) => Key::Insert,
+        (b'~', 3) => Key::Delete,
+        (b'~', 5) => Key::PageUp,
+        (b'~', 6) => Key::PageDown,
+        (b'~', 11..=15) => Key::F(number as u8 - 10),
+        (b'~', 17..=21) => Key::F(number as u8 - 11),
+        (b'~', 23..=24) => Key::F(number as u8 - 12),
+        (b'u', _) => return kitty_key(number, mods),
+        _ => Key::Unknown,
+    };
+    with_mods(key, mods)
+}
+
+/// A mouse event of SGR mouse reporting, CSI < <button> ; <col> ; <row> M, m on release
+fn sgr_mouse(params: &str, last: u8) -> Key {
+    let params = params[1..]
+        .split(';')
+        .map(|n| n.parse::<usize>().ok())
+        .collect::<Option<Vec<usize>>>();
+    let (button, col, row) = match params.as_deref() {
+        Some([button, col, row]) => (*button, *col, *row),
+        _ => return Key::Unknown,
+    };
+    // the low bits are the button, 32 is set for a drag and 64 for the wheel, Shift, Alt and
+    // Ctrl are 4, 8 and 16
+    let kind = match (button & !0b11100, last) {
+        (64, _) => MouseKind::WheelUp,
+        (65, _) => MouseKind::WheelDown,
+        (0, b'M') => MouseKind::Click,
+        _ => MouseKind::Other,
+    };
+    Key::Mouse(Mouse {
+        kind,
+        row: row.saturating_sub(1),
+        col: col.saturating_sub(1),
+    })
+}
+
+/// The key of an SS3 sequence, ESC O <final>, sent by some terminals for the arrows, Home, End
+/// and F1 to F4
+fn ss3_key(last: u8) -> Key {
+    match last {
+        b'A' | b'B' | b'C' | b'D' | b'H' | b'F' | b'P'..=b'S' => csi_key("", last),
+        _ => Key::Unknown,
+    }
+}
+
+/// A key that isn't an escape sequence: a control character or a UTF-8 character
+fn parse_char(bytes: &[u8], complete: bool) -> Option<(Key, usize)> {
+    let first = *bytes.first()?;
+    let key = match first {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0 => Key::Ctrl(' '),
+        1..=26 => Key::Ctrl((b'a' + first - 1) as char),
+        28..=31 => Key::Ctrl((b'\\' + first - 28) as char),
+        _ => {
+            let len = match first {
+                0b11000000..=0b11011111 => 2,
+                0b11100000..=0b11101111 => 3,
+                0b11110000..=0b11110111 => 4,
+                _ => 1,
+            };
+            if bytes.len() < len {
+                // the rest of the character is still coming
+                return if complete {
+                    Some((Key::Unknown, bytes.len()))
+                } else {
+                    None
+                };
+            }
+            return match from_utf8(&bytes[..len]).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Some((Key::Char(c), len)),
+                None => Some((Key::Unknown, 1)),
+            };
+        }
+    };
+    Some((key, 1))
+}
+
+/// A CSI sequence, or the whole bracketed paste that starts with one
+fn parse_csi(bytes: &[u8], complete: bool) -> Option<(Key, usize)> {
+    // parameters, then the final byte
+    let Some(end) = (2..bytes.len()).find(|i| (0x40..=0x7e).contains(&bytes[*i])) else {
+        return if complete {
+            Some((Key::Unknown, bytes.len()))
+        } else {
+            None
+        };
+    };
+    let params = String::from_utf8_lossy(&bytes[2..end]);
+    if params.starts_with('<') && matches!(bytes[end], b'M' | b'm') {
+        return Some((sgr_mouse(&params, bytes[end]), end + 1));
+    }
+    if bytes[end] != b'~' || params != PASTE_START {
+        return Some((csi_key(&params, bytes[end]), end + 1));
+    }
+    let paste = &bytes[end + 1..];
+    let (len, used) = match paste.windows(PASTE_END.len()).position(|w| w == PASTE_END) {
+        Some(len) => (len, end + 1 + len + PASTE_END.len()),
+        None if complete => (paste.len(), bytes.len()),
+        None => return None,
+    };
+    let (chars, _) = util::parse_utf8(&paste[..len], &Vec::new());
+    Some((Key::Paste(chars), used))
+}
+
+/// Decode the first key of bytes
+///
+/// returns
+///  the key and how many bytes it takes, None if the bytes are the start of a longer sequence,
+///  unless complete says nothing more is coming
+pub fn parse(bytes: &[u8], complete: bool) -> Option<(Key, usize)> {
+    if *bytes.first()? != 0x1b {
+        return parse_char(bytes, complete);
+    }
+    match bytes.get(1) {
+        None if complete => Some((Key::Esc, 1)),
+        None => None,
+        Some(b'[') => parse_csi(bytes, complete),
+        Some(b'O') => match bytes.get(2) {
+            Some(last) => Some((ss3_key(*last), 3)),
+            None if complete => Some((Key::Alt('O'), 2)),
+            None => None,
+        },
+        Some(0x1b) => Some((Key::Esc, 1)),
+        Some(_) => {
+            let (key, len) = parse_char(&bytes[1..], complete)?;
+            Some((with_alt(key), len + 1))
+        }
+    }
+}
+
+impl KeyReader {
+    /// Whether a key can be read within timeout milliseconds, -1 waits forever
+    pub fn ready(&self, timeout: i32) -> bool {
+        !self.buf.is_empty() || util::fd_ready(self.fd, timeout)
+    }
+
+    /// Read what's there, waiting for it if there's nothing. A read interrupted by a signal, e.g.
+    /// SIGWINCH, is tried again.
+    fn fill(&mut self) -> io::Result<()> {
+        let mut raw = [0_u8; 256];
+        loop {
+            let len = unsafe { read(self.fd, raw.as_mut_ptr() as *mut c_void, raw.len()) };
+            if len > 0 {
+                self.buf.extend(&raw[..len as usize]);
+                return Ok(());
+            }
+            if len == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            let err = io::Error::last_os_error();
+            match err.kind() {
+                io::ErrorKind::Interrupted => {}
+                io::ErrorKind::WouldBlock => {
+                    util::fd_ready(self.fd, -1);
+                }
+                _ => return Err(err),
+            }
+        }
+    }
+
+    /// Wait for the next key. An escape sequence that doesn't complete in time is taken as it
+    /// is, so a lone ESC is the Esc key. A paste is read until its end however long it takes.
+    /// Once stdin is closed, e.g. the terminal is gone, it's Closed.
+    pub fn read_key(&mut self) -> Key {
+        loop {
+            if let Some((key, len)) = parse(&self.buf, false) {
+                self.buf.drain(..len);
+                return self.double_click(key);
+            }
+            let pasting = self
+                .buf
+                .starts_with(format!("\x1b[{}~", PASTE_START).as_bytes());
+            if !self.buf.is_empty() && !pasting && !util::fd_ready(self.fd, ESC_TIMEOUT) {
+                if let Some((key, len)) = parse(&self.buf, true) {
+                    self.buf.drain(..len);
+                    return self.double_click(key);
+                }
+            }
+            if self.fill().is_err() {
+                return Key::Closed;
+            }
+        }
+    }
+
+    /// A click soon after another on the same cell is a double click
+    fn double_click(&mut self, key: Key) -> Key {
+        let Key::Mouse(mut mouse) = key else {
+            return key;
+        };
+        if mouse.kind == MouseKind::Click {
+            let now = Instant::now();
+            match self.last_click.take() {
+                Some((at, row, col))
+                    if now - at < DOUBLE_CLICK && (row, col) == (mouse.row, mouse.col) =>
+                {
+                    mouse.kind = MouseKind::DoubleClick;
+                }
+                _ => self.last_click = Some((now, mouse.row, mouse.col)),
+            }
+        }
+        Key::Mouse(mouse)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keys(bytes: &[u8]) -> Vec<Key> {
+        let mut keys = Vec::new();
+        let mut i = 0;
+        while let Some((key, len)) = parse(&bytes[i..], true) {
+            keys.push(key);
+            i += len;
+        }
+        keys
+    }
+
+    fn modified(key: Key, shift: bool, alt: bool, ctrl: bool) -> Key {
+        Key::Modified(Box::new(key), Mods { shift, alt, ctrl })
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            keys("jé我\n\t\x7f\x04\x1c".as_bytes()),
+            [
+                Key::Char('j'),
+                Key::Char('é'),
+                Key::Char('我'),
+                Key::Enter,
+                Key::Tab,
+                Key::Backspace,
+                Key::Ctrl('d'),
+                Key::Ctrl('\\'),
+            ]
+        );
+        // CSI and SS3
+        assert_eq!(
+            keys(b"\x1b[A\x1bOB\x1b[H\x1b[4~\x1b[5~\x1b[3~\x1bOP\x1b[15~\x1b[24~\x1b[Z\x1b[99x"),
+            [
+                Key::Up,
+                Key::Down,
+                Key::Home,
+                Key::End,
+                Key::PageUp,
+                Key::Delete,
+                Key::F(1),
+                Key::F(5),
+                Key::F(12),
+                Key::BackTab,
+                Key::Unknown,
+            ]
+        );
+        // modifiers and Alt
+        assert_eq!(
+            keys(b"\x1b[1;5D\x1b[1;2A\x1b[3;3~\x1bb\x1bB\x1b\x7f"),
+            [
+                modified(Key::Left, false, false, true),
+                modified(Key::Up, true, false, false),
+                modified(Key::Delete, false, true, false),
+                Key::Alt('b'),
+                Key::Alt('B'),
+                modified(Key::Backspace, false, true, false),
+            ]
+        );
+        // kitty keyboard protocol
+        assert_eq!(
+            keys(b"\x1b[27u\x1b[97;5u\x1b[97;3u\x1b[97;2u\x1b[13;5u\x1b[106;1:1u"),
+            [
+                Key::Esc,
+                Key::Ctrl('a'),
+                Key::Alt('a'),
+                Key::Char('A'),
+                modified(Key::Enter, false, false, true),
+                Key::Char('j'),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_incomplete() {
+        // more may be coming
+        assert_eq!(parse(b"\x1b", false), None);
+        assert_eq!(parse(b"\x1b[1;", false), None);
+        assert_eq!(parse(&"我".as_bytes()[..2], false), None);
+        // nothing more is coming, a lone ESC is the Esc key
+        assert_eq!(parse(b"\x1b", true), Some((Key::Esc, 1)));
+        assert_eq!(parse(b"\x1b\x1b[A", false), Some((Key::Esc, 1)));
+        assert_eq!(parse(b"\x1b[1;", true), Some((Key::Unknown, 4)));
+    }
+
+    #[test]
+    fn test_parse_mouse() {
+        let mouse = |kind, row, col| Key::Mouse(Mouse { kind, row, col });
+        assert_eq!(
+            keys(b"\x1b[<0;3;5M\x1b[<0;3;5m\x1b[<64;1;1M\x1b[<81;10;2M\x1b[<32;4;4M\x1b[<0;1M"),
+            [
+                mouse(MouseKind::Click, 4, 2),
+                mouse(MouseKind::Other, 4, 2),
+                mouse(MouseKind::WheelUp, 0, 0),
+                mouse(MouseKind::WheelDown, 1, 9),
+                mouse(MouseKind::Other, 3, 3),
+                Key::Unknown,
+            ]
+        );
+
+        let mut reader = KeyReader::default();
+        let click = || mouse(MouseKind::Click, 0, 0);
+        assert_eq!(reader.double_click(click()), click());
+        assert_eq!(
+            reader.double_click(click()),
+            mouse(MouseKind::DoubleClick, 0, 0)
+        );
+        // a third click starts over
+        assert_eq!(reader.double_click(click()), click());
+        assert_eq!(
+            reader.double_click(mouse(MouseKind::Click, 0, 1)),
+            mouse(MouseKind::Click, 0, 1)
+        );
+    }
+
+    #[test]
+    fn test_parse_paste() {
+        assert_eq!(
+            keys(b"\x1b[200~a\nb\x1b[D\x1b[201~q"),
+            [Key::Paste("a\nb\x1b[D".chars().collect()), Key::Char('q')]
+        );
+        assert_eq!(parse(b"\x1b[200~abc", false), None);
+    }
+
+    #[test]
+    fn test_read_key() {
+        use libc::{pipe2, O_NONBLOCK};
+        use std::fs::File;
+        use std::io::Write;
+        use std::os::fd::FromRawFd;
+        use std::thread;
+
+        // the keys come from a non-blocking pipe, an empty one is waited for
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { pipe2(fds.as_mut_ptr(), O_NONBLOCK) }, 0);
+        let (_read_end, mut write_end) =
+            unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        let mut reader = KeyReader {
+            fd: fds[0],
+            ..KeyReader::default()
+        };
+        let writer = thread::spawn(move || {
+            let pause = Duration::from_millis(ESC_TIMEOUT as u64 * 2);
+            thread::sleep(pause);
+            write_end.write_all(b"j\x1b[200~ab").unwrap();
+            // a paste in pieces further apart than ESC_TIMEOUT is still one paste
+            thread::sleep(pause);
+            write_end.write_all(b"c\x1b[201~\x1b").unwrap();
+            write_end
+        });
+        assert_eq!(reader.read_key(), Key::Char('j'));
+        assert_eq!(reader.read_key(), Key::Paste("abc".chars().collect()));
+        // the pipe is still open, ESC is taken as Esc once nothing follows it in time
+        let write_end = writer.join().unwrap();
+        assert_eq!(reader.read_key(), Key::Esc);
+        // then there's nothing more to read
+        drop(write_end);
+        assert_eq!(reader.read_key(), Key::Closed);
+    }
+}
@@ -0,0 +1,181 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::util;
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+
+const JUMPLIST_LEN: usize = 100;
+
+/// A visited directory, with the name of the entry the cursor was on when leaving it
+#[derive(Clone, PartialEq, Debug)]
+pub struct Jump {
+    pub path: PathBuf,
+    pub cursor: String,
+}
+
+/// Every directory trans has been to, like vim's jumplist. Saved as "<cursor>\t<path>" lines,
+/// both escaped with util::escape, the last line is where the last session ended.
+pub struct JumpList {
+    file: Option<PathBuf>,
+    jumps: Vec<Jump>,
+    // the jump we are at, back and forward move it
+    index: usize,
+}
+
+impl JumpList {
+    pub fn load(file: Option<PathBuf>) -> JumpList {
+        let mut jumplist = JumpList {
+            file,
+            jumps: Vec::new(),
+            index: 0,
+        };
+        let content = match &jumplist.file {
+            Some(file) => read_to_string(file).unwrap_or_default(),
+            None => String::new(),
+        };
+        for line in content.lines() {
+            if let Some((cursor, path)) = line.split_once('\t') {
+                jumplist.jumps.push(Jump {
+                    path: PathBuf::from(util::unescape(path)),
+                    cursor: util::unescape(cursor),
+                });
+            }
+        }
+        jumplist.index = jumplist.jumps.len().saturating_sub(1);
+        jumplist
+    }
+
+    /// Save the jumps up to the current one, so the next session continues from here with the
+    /// cursor on the same entry
+    pub fn save(&self, cursor: &str) -> io::Result<()> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let mut content = String::new();
+        for (i, jump) in self.jumps.iter().enumerate().take(self.index + 1) {
+            let cursor = if i == self.index {
+                cursor
+            } else {
+                &jump.cursor
+            };
+            content.push_str(&format!(
+                "{}\t{}\n",
+                util::escape(cursor),
+                util::escape(&jump.path.to_string_lossy())
+            ));
+        }
+        util::write_atomic(file, content.as_bytes())
+    }
+
+    /// Where the last session ended
+    pub fn last(&self) -> Option<&Jump> {
+        self.jumps.last()
+    }
+
+    /// Remember where the cursor is before leaving the current directory
+    pub fn leave(&mut self, cursor: &str) {
+        if let Some(jump) = self.jumps.get_mut(self.index) {
+            jump.cursor = cursor.to_string();
+        }
+    }
+
+    /// A new directory is visited, the jumps after the current one are dropped
+    pub fn visit(&mut self, path: PathBuf) {
+        if self
+            .jumps
+            .get(self.index)
+            .is_some_and(|jump| jump.path == path)
+        {
+            return;
+        }
+        self.jumps.truncate(self.index + 1);
+        self.jumps.push(Jump {
+            path,
+            cursor: String::new(),
+        });
+        if self.jumps.len() > JUMPLIST_LEN {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len() - 1;
+    }
+
+    pub fn back(&mut self) -> Option<Jump> {
+        if self.index == 0 || self.index >= self.jumps.len() {
+            return None;
+        }
+        self.index -= 1;
+        Some(self.jumps[self.index].clone())
+    }
+
+    pub fn forward(&mut self) -> Option<Jump> {
+        if self.index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.jumps[self.index].clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::random_dir_wcontent;
+
+    #[test]
+    fn test_jumplist() {
+        let (_, _, root_dir, _cd) = random_dir_wcontent();
+        let file = PathBuf::from(format!("/tmp/{}/state/jumplist", root_dir));
+        let mut jumplist = JumpList::load(Some(file.clone()));
+        assert!(jumplist.back().is_none());
+
+        jumplist.visit(PathBuf::from("/a"));
+        jumplist.leave("x");
+        jumplist.visit(PathBuf::from("/b"));
+        jumplist.leave("y");
+        jumplist.visit(PathBuf::from("/c"));
+        let back = jumplist.back().expect("failed to go back");
+        assert_eq!(back.path, PathBuf::from("/b"));
+        assert_eq!(back.cursor, "y");
+        assert_eq!(jumplist.forward().unwrap().path, PathBuf::from("/c"));
+        assert!(jumplist.forward().is_none());
+
+        // visiting after going back drops the jumps after it
+        jumplist.back();
+        jumplist.back();
+        jumplist.visit(PathBuf::from("/d"));
+        assert!(jumplist.forward().is_none());
+        assert_eq!(jumplist.back().unwrap().path, PathBuf::from("/a"));
+        jumplist.save("z").expect("failed to save jumplist");
+
+        // the session ended at /a
+        let jumplist = JumpList::load(Some(file.clone()));
+        assert_eq!(
+            jumplist.last(),
+            Some(&Jump {
+                path: PathBuf::from("/a"),
+                cursor: "z".to_string(),
+            })
+        );
+
+        // tabs and newlines in the names don't break the lines
+        let mut jumplist = JumpList::load(Some(file.clone()));
+        jumplist.visit(PathBuf::from("/new\nline\tand tab\\t"));
+        jumplist.save("a\tb\nc\\").expect("failed to save jumplist");
+        let jumplist = JumpList::load(Some(file));
+        assert_eq!(
+            jumplist.last(),
+            Some(&Jump {
+                path: PathBuf::from("/new\nline\tand tab\\t"),
+                cursor: "a\tb\nc\\".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,312 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::input::{Key, Mods};
+use std::collections::VecDeque;
+
+const KILL_RING_LEN: usize = 16;
+
+/// A change to the prompt text, readline style
+#[derive(Clone, PartialEq, Debug)]
+pub enum Edit {
+    // typed or pasted text
+    Insert(Vec<char>),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    // Alt + B and Alt + F
+    WordLeft,
+    WordRight,
+    // Ctrl + W, up to the whitespace before the cursor
+    KillWordBack,
+    // Alt + D
+    KillWordForward,
+    // Ctrl + U
+    KillToStart,
+    // Ctrl + K
+    KillToEnd,
+    // Ctrl + Y
+    Yank,
+    // Alt + Y, right after a yank, replaces it with an older kill
+    YankPop,
+}
+
+/// Line editing shared by all the prompts, the edits are applied to a text and a cursor position.
+/// The kill ring is shared as well, what's killed in one prompt can be yanked in another.
+#[derive(Default)]
+pub struct LineEditor {
+    kill_ring: VecDeque<Vec<char>>,
+    // the last edit was a kill, another kill adds to the same kill ring entry
+    killing: bool,
+    // the text range inserted by the last yank and which kill it was
+    yanked: Option<(usize, usize, usize)>,
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Where the word before pos starts, skipping what isn't a word first
+fn word_start(txt: &[char], mut pos: usize, word: fn(char) -> bool) -> usize {
+    while pos > 0 && !word(txt[pos - 1]) {
+        pos -= 1;
+    }
+    while pos > 0 && word(txt[pos - 1]) {
+        pos -= 1;
+    }
+    pos
+}
+
+/// Where the word after pos ends, skipping what isn't a word first
+fn word_end(txt: &[char], mut pos: usize) -> usize {
+    while pos < txt.len() && !is_word(txt[pos]) {
+        pos += 1;
+    }
+    while pos < txt.len() && is_word(txt[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Pasted text goes in the prompt as is, except that line breaks at the end are dropped and the
+/// other control characters become spaces
+fn pasted(paste: &[char]) -> Vec<char> {
+    let len = paste.len()
+        - paste
+            .iter()
+            .rev()
+            .take_while(|c| **c == '\n' || **c == '\r')
+            .count();
+    paste[..len]
+        .iter()
+        .map(|c| if c.is_control() { ' ' } else { *c })
+        .collect()
+}
+
+/// The edit a key makes in a prompt, None if it doesn't edit, e.g. Enter
+pub fn key_edit(key: &Key) -> Option<Edit> {
+    let word_motion = |mods: &Mods| mods.ctrl || mods.alt;
+    let edit = match key {
+        Key::Char(c) => Edit::Insert(vec![*c]),
+        Key::Paste(paste) => Edit::Insert(pasted(paste)),
+        Key::Backspace | Key::Ctrl('h') => Edit::Backspace,
+        Key::Delete | Key::Ctrl('d') => Edit::Delete,
+        Key::Left | Key::Ctrl('b') => Edit::Left,
+        Key::Right | Key::Ctrl('f') => Edit::Right,
+        Key::Home | Key::Ctrl('a') => Edit::Home,
+        Key::End | Key::Ctrl('e') => Edit::End,
+        Key::Alt('b') => Edit::WordLeft,
+        Key::Alt('f') => Edit::WordRight,
+        Key::Modified(key, mods) if **key == Key::Left && word_motion(mods) => Edit::WordLeft,
+        Key::Modified(key, mods) if **key == Key::Right && word_motion(mods) => Edit::WordRight,
+        Key::Modified(key, mods) if **key == Key::Backspace && mods.alt => Edit::KillWordBack,
+        Key::Ctrl('w') => Edit::KillWordBack,
+        Key::Alt('d') => Edit::KillWordForward,
+        Key::Ctrl('u') => Edit::KillToStart,
+        Key::Ctrl('k') => Edit::KillToEnd,
+        Key::Ctrl('y') => Edit::Yank,
+        Key::Alt('y') => Edit::YankPop,
+        _ => return None,
+    };
+    Some(edit)
+}
+
+impl LineEditor {
+    /// Apply an edit to the text with the cursor at pos
+    pub fn edit(&mut self, txt: &mut Vec<char>, pos: &mut usize, edit: Edit) {
+        *pos = (*pos).min(txt.len());
+        let killing = self.killing;
+        let yanked = self.yanked.take();
+        self.killing = false;
+        match edit {
+            Edit::Insert(chars) => {
+                let len = chars.len();
+                txt.splice(*pos..*pos, chars);
+                *pos += len;
+            }
+            Edit::Backspace if *pos > 0 => {
+                txt.remove(*pos - 1);
+                *pos -= 1;
+            }
+            Edit::Delete if *pos < txt.len() => {
+                txt.remove(*pos);
+            }
+            Edit::Backspace | Edit::Delete => {}
+            Edit::Left => *pos = pos.saturating_sub(1),
+            Edit::Right => *pos = (*pos + 1).min(txt.len()),
+            Edit::Home => *pos = 0,
+            Edit::End => *pos = txt.len(),
+            Edit::WordLeft => *pos = word_start(txt, *pos, is_word),
+            Edit::WordRight => *pos = word_end(txt, *pos),
+            Edit::KillWordBack => {
+                let start = word_start(txt, *pos, |c| !c.is_whitespace());
+                self.kill_ring_add(txt.drain(start..*pos).collect(), killing, true);
+                *pos = start;
+            }
+            Edit::KillToStart => {
+                self.kill_ring_add(txt.drain(..*pos).collect(), killing, true);
+                *pos = 0;
+            }
+            Edit::KillWordForward => {
+                let end = word_end(txt, *pos);
+                self.kill_ring_add(txt.drain(*pos..end).collect(), killing, false);
+            }
+            Edit::KillToEnd => {
+                self.kill_ring_add(txt.drain(*pos..).collect(), killing, false);
+            }
+            Edit::Yank => {
+                if let Some(kill) = self.kill_ring.front() {
+                    txt.splice(*pos..*pos, kill.iter().cloned());
+                    self.yanked = Some((*pos, *pos + kill.len(), 0));
+                    *pos += kill.len();
+                }
+            }
+            Edit::YankPop => {
+                if let Some((start, end, index)) = yanked {
+                    let index = (index + 1) % self.kill_ring.len().max(1);
+                    let kill = self.kill_ring.get(index).cloned().unwrap_or_default();
+                    txt.splice(start..end, kill.iter().cloned());
+                    self.yanked = Some((start, start + kill.len(), index));
+                    *pos = start + kill.len();
+                }
+            }
+        }
+    }
+
+    /// A kill, consecutive kills make a single kill ring entry, like readline
+    fn kill_ring_add(&mut self, killed: Vec<char>, append: bool, backward: bool) {
+        self.killing = true;
+        if killed.is_empty() {
+            return;
+        }
+        match self.kill_ring.front_mut() {
+            Some(last) if append => {
+                if backward {
+                    last.splice(0..0, killed);
+                } else {
+                    last.extend(killed);
+                }
+            }
+            _ => {
+                self.kill_ring.push_front(killed);
+                self.kill_ring.truncate(KILL_RING_LEN);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    /// Type the keys in a prompt with "foo bar.baz", the cursor at the end
+    fn typed(editor: &mut LineEditor, keys: &str) -> (String, usize) {
+        let mut txt = chars("foo bar.baz");
+        let mut pos = txt.len();
+        let mut keys = keys.as_bytes();
+        while let Some((key, len)) = input::parse(keys, true) {
+            if let Some(edit) = key_edit(&key) {
+                editor.edit(&mut txt, &mut pos, edit);
+            }
+            keys = &keys[len..];
+        }
+        (txt.iter().collect(), pos)
+    }
+
+    #[test]
+    fn test_key_edit() {
+        let ctrl = Mods {
+            ctrl: true,
+            ..Mods::default()
+        };
+        assert_eq!(key_edit(&Key::Char('a')), Some(Edit::Insert(chars("a"))));
+        assert_eq!(key_edit(&Key::Ctrl('a')), Some(Edit::Home));
+        assert_eq!(
+            key_edit(&Key::Modified(Box::new(Key::Left), ctrl)),
+            Some(Edit::WordLeft)
+        );
+        assert_eq!(key_edit(&Key::Enter), None);
+        assert_eq!(key_edit(&Key::Ctrl('r')), None);
+        // control characters in a paste don't act as keys
+        assert_eq!(
+            key_edit(&Key::Paste(chars("a\tb\x1b[Dc\n"))),
+            Some(Edit::Insert(chars("a b [Dc")))
+        );
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut editor = LineEditor::default();
+        assert_eq!(typed(&mut editor, "\x01"), ("foo bar.baz".to_string(), 0));
+        assert_eq!(typed(&mut editor, "\x1bb"), ("foo bar.baz".to_string(), 8));
+        assert_eq!(
+            typed(&mut editor, "\x1bb\x1bb\x1bf"),
+            ("foo bar.baz".to_string(), 7)
+        );
+        assert_eq!(
+            typed(&mut editor, "\x01\x1b[3~\x05x"),
+            ("oo bar.bazx".to_string(), 11)
+        );
+        assert_eq!(typed(&mut editor, "\x17"), ("foo ".to_string(), 4));
+        assert_eq!(typed(&mut editor, "\x1b\x7f"), ("foo ".to_string(), 4));
+        assert_eq!(
+            typed(&mut editor, "\x02\x02\x08\x06\x04"),
+            ("foo bar.a".to_string(), 9)
+        );
+        assert_eq!(typed(&mut editor, "\x1bb\x0b"), ("foo bar.".to_string(), 8));
+        assert_eq!(typed(&mut editor, "\x1bb\x15"), ("baz".to_string(), 0));
+        assert_eq!(
+            typed(&mut editor, "\x1b[H\x1bd"),
+            (" bar.baz".to_string(), 0)
+        );
+        assert_eq!(
+            typed(&mut editor, "\x1b[1;5D\x1b[1;5D"),
+            ("foo bar.baz".to_string(), 4)
+        );
+        assert_eq!(
+            typed(&mut editor, "\x1b[200~ qux\n\x1b[201~"),
+            ("foo bar.baz qux".to_string(), 15)
+        );
+    }
+    #[test]
+    fn test_kill_ring() {
+        let mut editor = LineEditor::default();
+        // consecutive kills are yanked back together
+        assert_eq!(
+            typed(&mut editor, "\x17\x17\x19"),
+            ("foo bar.baz".to_string(), 11)
+        );
+        assert_eq!(editor.kill_ring.len(), 1);
+
+        // yank pop goes through the older kills
+        let mut editor = LineEditor::default();
+        typed(&mut editor, "\x15");
+        assert_eq!(
+            typed(&mut editor, "\x1bb\x0b\x01\x19"),
+            ("bazfoo bar.".to_string(), 3)
+        );
+        assert_eq!(
+            // the kill ring is [baz, baz, foo bar.baz] by now
+            typed(&mut editor, "\x1bb\x0b\x01\x19\x1by\x1by"),
+            ("foo bar.bazfoo bar.".to_string(), 11)
+        );
+        // only right after a yank
+        assert_eq!(
+            typed(&mut editor, "\x01\x1by"),
+            ("foo bar.baz".to_string(), 0)
+        );
+    }
+}
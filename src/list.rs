@@ -0,0 +1,111 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+/// A full screen list of lines to pick from, e.g. the bookmarks
+#[derive(Default)]
+pub struct ListView {
+    pub title: String,
+    pub items: Vec<String>,
+    pub cursor: usize,
+    pub window_start: usize,
+}
+
+impl ListView {
+    pub fn new(title: &str, items: Vec<String>) -> ListView {
+        ListView {
+            title: title.to_string(),
+            items,
+            cursor: 0,
+            window_start: 0,
+        }
+    }
+
+    /// Keep the cursor inside the window of this height
+    fn scroll(&mut self, height: usize) {
+        if self.cursor < self.window_start {
+            self.window_start = self.cursor;
+        } else if height > 0 && self.cursor >= self.window_start + height {
+            self.window_start = self.cursor + 1 - height;
+        }
+    }
+
+    pub fn up(&mut self, height: usize) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+        }
+        self.scroll(height);
+    }
+
+    pub fn down(&mut self, height: usize) {
+        if self.cursor + 1 < self.items.len() {
+            self.cursor += 1;
+        }
+        self.scroll(height);
+    }
+
+    pub fn top(&mut self) {
+        self.cursor = 0;
+        self.window_start = 0;
+    }
+
+    pub fn bottom(&mut self, height: usize) {
+        self.cursor = self.items.len().saturating_sub(1);
+        self.scroll(height);
+    }
+
+    pub fn pageup(&mut self, height: usize) {
+        self.cursor = self.cursor.saturating_sub(height / 2);
+        self.scroll(height);
+    }
+
+    pub fn pagedown(&mut self, height: usize) {
+        self.cursor = (self.cursor + height / 2).min(self.items.len().saturating_sub(1));
+        self.scroll(height);
+    }
+
+    /// Remove the item under the cursor
+    pub fn remove(&mut self, height: usize) {
+        if self.cursor < self.items.len() {
+            self.items.remove(self.cursor);
+        }
+        if self.cursor >= self.items.len() {
+            self.cursor = self.items.len().saturating_sub(1);
+        }
+        self.scroll(height);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_list_view() {
+        let items = (0..10).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut list = ListView::new("test", items);
+        let height = 4;
+        list.bottom(height);
+        assert_eq!(list.cursor, 9);
+        assert_eq!(list.window_start, 6);
+        list.pageup(height);
+        assert_eq!(list.cursor, 7);
+        list.top();
+        list.up(height);
+        assert_eq!(list.cursor, 0);
+        list.pagedown(height);
+        list.down(height);
+        list.down(height);
+        assert_eq!(list.cursor, 4);
+        assert_eq!(list.window_start, 1);
+        list.remove(height);
+        assert_eq!(list.items[list.cursor], "5");
+        list.bottom(height);
+        list.remove(height);
+        assert_eq!(list.cursor, 7);
+    }
+}
@@ -0,0 +1,284 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::event::Waker;
+use crate::tab::Entry;
+use crate::work::Job;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs::read_dir;
+use std::mem::take;
+use std::path::{Path, PathBuf};
+use std::sync::atomic;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+// entries sent at once, or what's been read in BATCH_TIME if it's fewer
+const BATCH: usize = 4096;
+const BATCH_TIME: Duration = Duration::from_millis(50);
+
+/// Compare names ignoring case, without making lowercase copies of them
+pub fn by_name(a: &str, b: &str) -> Ordering {
+    if a.is_ascii() && b.is_ascii() {
+        for (x, y) in a.bytes().zip(b.bytes()) {
+            match x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase()) {
+                Ordering::Equal => {}
+                order => return order,
+            }
+        }
+        return a.len().cmp(&b.len());
+    }
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .cmp(b.chars().flat_map(char::to_lowercase))
+}
+
+/// The names in a directory, sorted ignoring case
+pub fn read_names(dir: &Path) -> Vec<String> {
+    read_some_names(dir, usize::MAX).0
+}
+
+/// The first limit names read from a directory, sorted ignoring case, and whether there are more
+pub fn read_some_names(dir: &Path, limit: usize) -> (Vec<String>, bool) {
+    let mut names = match read_dir(dir) {
+        Ok(entries) => entries
+            .map(|_e| match _e {
+                Ok(e) => match e.file_name().into_string() {
+                    Ok(filename) => filename,
+                    Err(filename_os) => filename_os.to_string_lossy().to_string(),
+                },
+                Err(_) => String::new(),
+            })
+            .take(limit.saturating_add(1))
+            .collect::<Vec<String>>(),
+        Err(_) => Vec::new(),
+    };
+    let more = names.len() > limit;
+    names.truncate(limit);
+
+    names.sort_by(|d1, d2| by_name(d1, d2));
+    (names, more)
+}
+
+/// The entries of the directory. In the tree view each expanded directory is followed by its
+/// entries, one level deeper.
+fn read_entries(dir: &Path, expanded: Option<&HashSet<PathBuf>>) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    add_entries(&mut entries, dir, "", 0, expanded);
+    entries
+}
+
+/// Add the entries of the directory at path in dir, and of its expanded directories
+fn add_entries(
+    entries: &mut Vec<Entry>,
+    dir: &Path,
+    path: &str,
+    depth: usize,
+    expanded: Option<&HashSet<PathBuf>>,
+) {
+    for name in read_names(&dir.join(path)) {
+        let entry = Entry {
+            path: if path.is_empty() {
+                name.into()
+            } else {
+                format!("{}/{}", path, name).into()
+            },
+            depth,
+        };
+        let expand = expanded.is_some_and(|expanded| expanded.contains(&dir.join(&*entry.path)));
+        let path = entry.path.clone();
+        entries.push(entry);
+        if expand {
+            add_entries(entries, dir, &path, depth + 1, expanded);
+        }
+    }
+}
+
+/// What the thread has read of a directory
+enum Read {
+    // entries read since the last batch, sorted, while it's shown as it's read
+    More(Vec<Entry>),
+    // all of it, sorted
+    All(Vec<Entry>),
+}
+
+/// A directory read by a background thread. A shown one is sent a batch at a time as it's read,
+/// so a huge one can be listed while it's read. Each batch is sorted by the thread and handed
+/// over, the thread doesn't keep it, and the sorted batches are merged into one once it's all
+/// read. Otherwise, and in the tree view, it comes all at once, sorted.
+pub struct Loader {
+    // batches read and not taken yet
+    pub entries: Vec<Entry>,
+    // all of it, sorted, once it's been read, unless it came in batches
+    pub all: Option<Vec<Entry>>,
+    // it's all been read, in batches or all at once
+    pub read: bool,
+    // how many have been read
+    pub count: usize,
+    // listed as it's read, or the listing stays as it is until it's all read
+    pub shown: bool,
+    // where the cursor goes once it's all read, unless it's moved from where it was then
+    pub select: Option<(String, usize)>,
+    // directories still to go into once it's all read, l with a count goes into the first entry
+    pub enter: usize,
+    job: Job<Read>,
+}
+
+impl Loader {
+    /// Start reading the directory, with the expanded directories in it for the tree view
+    pub fn start(
+        dir: PathBuf,
+        expanded: Option<HashSet<PathBuf>>,
+        shown: bool,
+        waker: Option<Waker>,
+    ) -> Loader {
+        let job = Job::stream(waker, move |cancel, stream| {
+            if expanded.is_some() {
+                stream.send(Read::All(read_entries(&dir, expanded.as_ref())));
+                return;
+            }
+            // what's been read since the last batch was sent, all of it if it isn't shown
+            let mut read = Vec::new();
+            if let Ok(entries) = read_dir(&dir) {
+                let mut sent = Instant::now();
+                for entry in entries.map_while(Result::ok) {
+                    if cancel.load(atomic::Ordering::Relaxed) {
+                        return;
+                    }
+                    let name = match entry.file_name().into_string() {
+                        Ok(name) => name,
+                        Err(name) => name.to_string_lossy().to_string(),
+                    };
+                    read.push(Entry::from(name));
+                    if shown && (read.len() >= BATCH || sent.elapsed() >= BATCH_TIME) {
+                        read.sort_by(|a, b| by_name(&a.path, &b.path));
+                        if !stream.send(Read::More(take(&mut read))) {
+                            return;
+                        }
+                        sent = Instant::now();
+                    }
+                }
+            }
+            read.sort_by(|a, b| by_name(&a.path, &b.path));
+            // a shown one's done once the job is
+            stream.send(if shown {
+                Read::More(read)
+            } else {
+                Read::All(read)
+            });
+        });
+        Loader {
+            entries: Vec::new(),
+            all: None,
+            read: false,
+            count: 0,
+            shown,
+            select: None,
+            enter: 0,
+            job,
+        }
+    }
+
+    /// Take in what the thread has read, waiting at most timeout for all of it
+    ///
+    /// returns
+    ///  whether anything was read, or it's all been read
+    pub fn poll(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut progress = false;
+        while !self.read {
+            match self
+                .job
+                .next(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(Read::More(batch)) => {
+                    self.count += batch.len();
+                    self.entries.extend(batch);
+                }
+                Ok(Read::All(all)) => {
+                    self.count = all.len();
+                    self.all = Some(all);
+                    self.read = true;
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                // all the batches have been sent, or the thread's gone without reading it
+                Err(RecvTimeoutError::Disconnected) => {
+                    if !self.shown {
+                        self.all = Some(Vec::new());
+                    }
+                    self.read = true;
+                }
+            }
+            progress = true;
+        }
+        progress
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::random_dir_wcontent;
+    use std::fs::{create_dir, File};
+
+    #[test]
+    fn test_loader() {
+        let (files, dirs, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        File::create(root.join(".hidden")).expect("failed to create file");
+        let mut loader = Loader::start(root.clone(), None, false, None);
+        loader.poll(Duration::from_secs(5));
+        assert_eq!(loader.count, files.len() + dirs.len() + 1);
+        // it's sent all at once when it isn't shown
+        assert!(loader.entries.is_empty());
+        let names = loader
+            .all
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path.to_string())
+            .collect::<Vec<String>>();
+        let mut all = [files, dirs.clone(), vec![String::from(".hidden")]].concat();
+        all.sort_by_key(|name| name.to_lowercase());
+        assert_eq!(names, all);
+        assert_eq!(by_name("B", "a"), Ordering::Greater);
+        assert_eq!(by_name("Äb", "äc"), Ordering::Less);
+        assert_eq!(by_name("ab", "A"), Ordering::Greater);
+
+        // the tree view's come sorted, with what's in the expanded directories
+        create_dir(root.join(&dirs[0]).join("inner")).expect("failed to create dir");
+        let expanded = HashSet::from([root.join(&dirs[0])]);
+        let mut loader = Loader::start(root.clone(), Some(expanded), true, None);
+        loader.poll(Duration::from_secs(5));
+        let all = loader.all.unwrap();
+        assert_eq!(&*all[0].path, ".hidden");
+        assert!(all
+            .iter()
+            .any(|entry| *entry.path == format!("{}/inner", dirs[0]) && entry.depth == 1));
+    }
+
+    #[test]
+    fn test_loader_batches() {
+        let (_, _, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir)).join("huge");
+        create_dir(&root).expect("failed to create dir");
+        for i in 0..BATCH + 10 {
+            File::create(root.join(format!("{:x}", i * 7919 % (BATCH + 10))))
+                .expect("failed to create file");
+        }
+        // a shown one comes in batches as it's read, each is sorted but they aren't merged
+        let mut loader = Loader::start(root, None, true, None);
+        assert!(loader.poll(Duration::from_secs(5)));
+        assert!(loader.read);
+        assert!(loader.all.is_none());
+        assert_eq!(loader.count, BATCH + 10);
+        assert!(loader
+            .entries
+            .windows(2)
+            .any(|pair| by_name(&pair[0].path, &pair[1].path).is_gt()));
+    }
+}
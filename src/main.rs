@@ -6,14 +6,36 @@
 ║ above copyright notice and this permission notice appear in all copies ║
 ╚═══════════════════════════════════════════════════════════════════════*/
 
+mod bookmark;
 mod browser;
 mod canvas;
+mod event;
+mod find;
+mod frecency;
+mod grep;
+mod history;
+mod input;
+mod jumplist;
+mod line_editor;
+mod list;
+mod load;
+mod matcher;
 mod ops;
+mod shell;
+mod tab;
 mod theme;
 mod util;
+mod walk;
+mod watch;
 mod widechar_width;
+mod work;
 
+use frecency::Frecency;
+use jumplist::JumpList;
+use ops::{consts, DestFormat, Exit};
+use shell::Shell;
 use std::env;
+use std::fs::File;
 use std::path::Path;
 use std::process::exit;
 
@@ -22,44 +44,51 @@ static VERSION: &str = "1.5.8";
 static HELP_MSG: &str = r###"
   transgender
     --dest <file>   File that transgender outputs the destination path to
+    --dest-format <lines|nul|json>
+                    Format of the destination output (default: lines). json has the exit
+                    reason, current path, the entry under the cursor and the marked entries
     -v, --version   Print current version
     -h, --help      Show this message
-    --sh            Print transgender configuration shell script
+    --sh [shell]    Print transgender configuration shell script for bash, zsh, fish, nu,
+                    pwsh or sh (default: detected from $SHELL)
     -c, --conf      Config file path (default: ~/.tsrc)
+    --last          Start where the last session ended
+    --choose-files <file>
+                    Pick files: <ENTER> writes the marked files or the file under the cursor to
+                    <file> (- for stdout) and exits
+    --choose-dir <file>
+                    Pick directories, like --choose-files. i picks the current directory
+    --print0        Separate the chosen paths with NUL instead of newline
+    --query <text>  Print the visited directory that best matches <text>, ranked by frecency
 
     Use transgender <DIR> to start transgender in DIR directory
+    Use transgender <FILE> to start transgender with the cursor on FILE
+
+    Exit status: 0 after i or entering a directory, 1 after q, 2 after picking in chooser mode,
+    3 when trans can't start, 4 when --query matches nothing
 
 "###;
 
-static SHELL_SCRIPT: &str = r###"function ts() {
-  dest_file=$(mktemp -t ts)
-  transgender $@ --dest "${dest_file}"
-  dest_dir=$(tail -n 1 "${dest_file}")
-  cd "${dest_dir}"
-  rm -f "${dest_file}"
+/// An argument that can't be used, said on stderr with the help since stdout may be eval'd
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprint!("{}", HELP_MSG);
+    exit(Exit::Error.code());
 }
 
-if [[ "$0" =~ .*bash$ ]];
-then
-  complete -o dirnames ts
-fi
-
-if [[ "$0" == "zsh" ]];
-then
-  autoload -U compinit
-  compinit
-  setopt globdots
-  zstyle ':completion:*:*:ts:*' file-patterns '*(-/):directories'
-fi
-"###;
-
 fn main() {
     let mut path = String::from(".");
+    let mut cursor_on: Option<String> = None;
+    let mut last_tabs = None;
     let mut dest_file: Option<String> = None;
     let mut config_path: String;
     let mut config_path_op: Option<&str> = None;
+    let mut choose: Option<(String, bool)> = None;
+    let mut print0 = false;
+    let mut dest_format = DestFormat::Lines;
 
-    let mut args = env::args();
+    // skip the program name
+    let mut args = env::args().skip(1).peekable();
     let print_help_message = || {
         print!("{}", HELP_MSG);
     };
@@ -79,26 +108,74 @@ fn main() {
             );
             exit(0);
         } else if s.eq("--sh") {
-            print!("{}", SHELL_SCRIPT);
+            let shell = match args.next_if(|arg| !arg.starts_with('-')) {
+                Some(name) => Shell::from(&name)
+                    .unwrap_or_else(|| usage_error(&format!("--sh: Unsupported shell {}", name))),
+                None => Shell::detect(),
+            };
+            print!("{}", shell.script());
             exit(0);
         } else if s.eq("--dest") {
             let _one_more = args.next();
             if _one_more.is_none() {
-                println!("--dest: Please specify the file path with --dest FILE");
-                print_help_message();
-                exit(0);
+                usage_error("--dest: Please specify the file path with --dest FILE");
             }
             dest_file = Some(_one_more.expect("Failed to unwrap destination file path"));
+        } else if s.eq("--dest-format") {
+            let _one_more = args.next();
+            let format = _one_more.as_deref().and_then(DestFormat::from);
+            if format.is_none() {
+                usage_error("--dest-format: Please specify one of lines, nul or json");
+            }
+            dest_format = format.expect("Failed to unwrap destination format");
+        } else if s.eq("--choose-files") || s.eq("--choose-dir") {
+            let _one_more = args.next();
+            if _one_more.is_none() {
+                usage_error(&format!(
+                    "{}: Please specify the output file with {} FILE",
+                    s, s
+                ));
+            }
+            choose = Some((
+                _one_more.expect("Failed to unwrap chooser output file"),
+                s.eq("--choose-dir"),
+            ));
+        } else if s.eq("--last") {
+            // the tabs of the last session, the jumplist has where it ended if it had none
+            let (tabs, current) = tab::load_tabs(util::state_file(consts::TABS_FILE));
+            let jumplist = JumpList::load(util::state_file(consts::JUMPLIST_FILE));
+            let last = tabs.get(current).or_else(|| jumplist.last());
+            if let Some(jump) = last.filter(|jump| jump.path.is_dir()) {
+                path = jump.path.to_string_lossy().to_string();
+                cursor_on = Some(jump.cursor.clone());
+            }
+            last_tabs = Some((tabs, current));
+        } else if s.eq("--query") {
+            let _one_more = args.next();
+            if _one_more.is_none() {
+                usage_error("--query: Please specify the text to match with --query TEXT");
+            }
+            let query = _one_more.expect("Failed to unwrap query");
+            let frecency = Frecency::load(util::state_file(consts::FRECENCY_FILE));
+            match frecency
+                .query(&query.chars().collect::<Vec<char>>())
+                .first()
+            {
+                Some(dir) => {
+                    println!("{}", dir.to_string_lossy());
+                    exit(0);
+                }
+                None => exit(Exit::NoMatch.code()),
+            }
+        } else if s.eq("--print0") {
+            print0 = true;
         } else if s.eq("-h") || s.eq("--help") {
             print_help_message();
             exit(0);
         } else if s.eq("-c") || s.eq("--config") {
             let _one_more = args.next();
-            let conf_err = || {
-                println!("-c/--config: Please provide the path to the configuration file");
-                print_help_message();
-                exit(0);
-            };
+            let conf_err =
+                || usage_error("-c/--config: Please provide the path to the configuration file");
             if _one_more.is_none() {
                 conf_err();
             }
@@ -108,17 +185,51 @@ fn main() {
             }
             config_path_op = Some(&config_path);
         } else {
-            // the starting path with no argument name
+            // the starting path with no argument name, a file is shown in its directory
             let p = Path::new(&s);
             if p.is_dir() {
                 path = String::from(p.to_str().expect("Failed to convert an existed path"));
+            } else if p.exists() {
+                let parent = match p.parent() {
+                    Some(parent) if parent != Path::new("") => parent,
+                    _ => Path::new("."),
+                };
+                path = String::from(parent.to_str().expect("Failed to convert an existed path"));
+                cursor_on = p.file_name().map(|name| name.to_string_lossy().to_string());
+            } else {
+                eprintln!("{}: No such file or directory", s);
+                exit(Exit::Error.code());
             }
         }
     }
 
+    // '-' means stdout, which has to be swapped out before anything is drawn
+    let chooser_out = choose.as_ref().map(|(file, _)| {
+        let out = if file == "-" {
+            util::stdout_to_tty()
+        } else {
+            File::create(file)
+        };
+        out.unwrap_or_else(|e| {
+            eprintln!("Failed to open {} for the chosen paths: {}", file, e);
+            exit(Exit::Error.code());
+        })
+    });
+
     // multiple arguments with random order
     let mut canvas = canvas::new(config_path_op);
     let mut browser = browser::new(&path, dest_file, config_path_op);
+    browser.set_dest_format(dest_format);
+    // the directory may still be being read
+    if let Some(name) = cursor_on {
+        browser.select(&name);
+    }
+    if let Some((tabs, current)) = last_tabs {
+        browser.restore_tabs(tabs, current);
+    }
+    if let (Some(out), Some((_, dirs))) = (chooser_out, choose) {
+        browser.set_chooser(out, dirs, print0);
+    }
 
     util::enter_albuf();
     browser.start_loop(&mut canvas);
@@ -0,0 +1,356 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::ops::SearchMode;
+use crate::walk;
+use regex_lite::{Regex, RegexBuilder};
+use std::cmp::Reverse;
+
+// scores are the same as fzf's
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+const BONUS_BOUNDARY: i64 = SCORE_MATCH / 2;
+const BONUS_CAMEL: i64 = BONUS_BOUNDARY - 1;
+const BONUS_CONSECUTIVE: i64 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+/// Bonus for matching the character at i, characters at the start of a word are worth more
+fn bonus(text: &[char], i: usize) -> i64 {
+    if i == 0 {
+        return BONUS_BOUNDARY;
+    }
+    let (prev, c) = (text[i - 1], text[i]);
+    if !prev.is_alphanumeric() && c.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if (prev.is_lowercase() && c.is_uppercase()) || (!prev.is_numeric() && c.is_numeric()) {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+/// fzf style fuzzy match, the pattern has to appear in the text in order, not necessarily next to
+/// each other. Smart case: it's case-insensitive unless the pattern has an uppercase letter.
+///
+/// returns
+///  the score, higher is better, and the character indices of the matched characters
+pub fn fuzzy_match(pattern: &[char], text: &str) -> Option<(i64, Vec<usize>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let case_sensitive = pattern.iter().any(|c| c.is_uppercase());
+    let text = text.chars().collect::<Vec<char>>();
+    let eq = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a == b || a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+
+    // find where the earliest full match ends
+    let mut p = 0;
+    let mut end = 0;
+    for (i, c) in text.iter().enumerate() {
+        if eq(*c, pattern[p]) {
+            p += 1;
+            if p == pattern.len() {
+                end = i;
+                break;
+            }
+        }
+    }
+    if p < pattern.len() {
+        return None;
+    }
+
+    // then walk back to find the shortest match ending there
+    let mut positions = vec![0; pattern.len()];
+    let mut p = pattern.len();
+    for i in (0..=end).rev() {
+        if eq(text[i], pattern[p - 1]) {
+            p -= 1;
+            positions[p] = i;
+            if p == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    for (p, &i) in positions.iter().enumerate() {
+        let mut b = bonus(&text, i);
+        if p == 0 {
+            b *= BONUS_FIRST_CHAR_MULTIPLIER;
+        } else {
+            let gap = (i - positions[p - 1] - 1) as i64;
+            if gap == 0 {
+                b = b.max(BONUS_CONSECUTIVE);
+            } else {
+                score += SCORE_GAP_START + SCORE_GAP_EXTENSION * (gap - 1);
+            }
+        }
+        score += SCORE_MATCH + b;
+    }
+    Some((score, positions))
+}
+
+/// Regex match with the character indices of every matched character, for highlighting
+pub fn regex_match(re: &Regex, text: &str) -> Option<Vec<usize>> {
+    let ranges = re
+        .find_iter(text)
+        .map(|m| m.range())
+        .filter(|r| !r.is_empty())
+        .collect::<Vec<_>>();
+    if ranges.is_empty() {
+        return if re.is_match(text) {
+            Some(Vec::new())
+        } else {
+            None
+        };
+    }
+    let positions = text
+        .char_indices()
+        .enumerate()
+        .filter(|(_, (b, _))| ranges.iter().any(|r| r.contains(b)))
+        .map(|(i, _)| i)
+        .collect();
+    Some(positions)
+}
+
+/// Whether the search text ends with an unescaped '\<c>', e.g. U\\\\\C does but U\\\\C doesn't
+fn ends_with_flag(search_txt: &[char], c: char) -> bool {
+    let len = search_txt.len();
+    if len < 2 || search_txt[len - 2..] != ['\\', c] {
+        return false;
+    }
+    let backslashes = search_txt[..len - 2]
+        .iter()
+        .rev()
+        .take_while(|c| **c == '\\')
+        .count();
+    backslashes % 2 == 0
+}
+
+/// Whether a letter of the search text is uppercase. Escapes of a regex, such as '\W', don't
+/// count.
+fn has_uppercase(search_txt: &[char], mode: SearchMode) -> bool {
+    if mode != SearchMode::Regex {
+        return search_txt.iter().any(|c| c.is_uppercase());
+    }
+    let mut i = 0;
+    while i < search_txt.len() {
+        match search_txt[i] {
+            // the escaped character
+            '\\' => i += 1,
+            c if c.is_uppercase() => return true,
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Build the regex for the search text. '\R', '\V' or '\G' at the start of it makes it a regex, a
+/// literal or a glob, otherwise mode decides. Smart case, like vim: case sensitive if there's an
+/// uppercase letter, '\C' at the end forces case sensitive and '\c' case insensitive. The filter,
+/// the find and the grep prompts build their regex with it too.
+pub fn search_regex(search_txt: &[char], mode: SearchMode) -> Result<Regex, regex_lite::Error> {
+    let (mode, mut search_txt) = match search_txt {
+        ['\\', 'R', rest @ ..] => (SearchMode::Regex, rest),
+        ['\\', 'V', rest @ ..] => (SearchMode::Literal, rest),
+        ['\\', 'G', rest @ ..] => (SearchMode::Glob, rest),
+        _ => (mode, search_txt),
+    };
+
+    let mut case_insensitive = None;
+    for (flag, insensitive) in [('C', false), ('c', true)] {
+        if search_txt.len() > 2 && ends_with_flag(search_txt, flag) {
+            search_txt = &search_txt[..search_txt.len() - 2];
+            case_insensitive = Some(insensitive);
+            break;
+        }
+    }
+    let case_insensitive = case_insensitive.unwrap_or_else(|| !has_uppercase(search_txt, mode));
+
+    let search = search_txt.iter().collect::<String>();
+    let pattern = match mode {
+        SearchMode::Regex => search,
+        SearchMode::Literal => regex_lite::escape(&search),
+        SearchMode::Glob => format!("^{}$", walk::glob_to_regex(&search)),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+}
+
+/// Items matching the pattern, fuzzy or regex, with the score, the index and the indices of the
+/// matched characters of each. The best fuzzy matches come first, items that match equally well
+/// stay in order. A regex match scores 0, an invalid regex matches everything.
+pub fn score<T: AsRef<str>>(
+    items: &[T],
+    pattern: &[char],
+    fuzzy: bool,
+) -> Vec<(i64, usize, Vec<usize>)> {
+    let mut hits: Vec<(i64, usize, Vec<usize>)> = Vec::new();
+    if fuzzy {
+        for (i, item) in items.iter().enumerate() {
+            if let Some((score, positions)) = fuzzy_match(pattern, item.as_ref()) {
+                hits.push((score, i, positions));
+            }
+        }
+        hits.sort_by_key(|hit| Reverse(hit.0));
+    } else {
+        // Regex can be invalid while the user is typing
+        let re = search_regex(pattern, SearchMode::Regex).ok();
+        for (i, item) in items.iter().enumerate() {
+            match &re {
+                Some(re) => {
+                    if let Some(positions) = regex_match(re, item.as_ref()) {
+                        hits.push((0, i, positions));
+                    }
+                }
+                None => hits.push((0, i, Vec::new())),
+            }
+        }
+    }
+    hits
+}
+
+/// Where the hits of items found later go among the ranked ones, with the scores of both from
+/// the best. The better ones come first, and of the ones that match equally well, the ranked ones
+/// come first.
+///
+/// returns
+///  for each hit of the merged list, whether it's one of the new ones
+pub fn merge_order(ranked: &[i64], more: &[i64]) -> Vec<bool> {
+    let mut order = Vec::with_capacity(ranked.len() + more.len());
+    let (mut i, mut j) = (0, 0);
+    while i < ranked.len() || j < more.len() {
+        let new = i == ranked.len() || (j < more.len() && more[j] > ranked[i]);
+        if new {
+            j += 1;
+        } else {
+            i += 1;
+        }
+        order.push(new);
+    }
+    order
+}
+
+/// Merge the new items into the ranked ones in the order from merge_order
+pub fn merge<T>(ranked: &mut Vec<T>, more: Vec<T>, order: &[bool]) {
+    let mut old = std::mem::take(ranked).into_iter();
+    let mut more = more.into_iter();
+    ranked.reserve(order.len());
+    for &new in order {
+        ranked.extend(if new { more.next() } else { old.next() });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        let (_, positions) = fuzzy_match(&chars("fb"), "foo_bar").expect("fb should match");
+        assert_eq!(positions, [0, 4]);
+        assert!(fuzzy_match(&chars("xyz"), "foo_bar").is_none());
+        assert!(fuzzy_match(&chars("rab"), "foo_bar").is_none());
+
+        // smart case
+        assert!(fuzzy_match(&chars("readme"), "README.md").is_some());
+        assert!(fuzzy_match(&chars("Readme"), "README.md").is_none());
+
+        // the shortest match is taken
+        let (_, positions) = fuzzy_match(&chars("ab"), "a_a_ab").expect("ab should match");
+        assert_eq!(positions, [4, 5]);
+
+        // consecutive and word boundary matches are better
+        let score = |p: &str, t: &str| fuzzy_match(&chars(p), t).unwrap().0;
+        assert!(score("src", "src/main.rs") > score("src", "some/rc"));
+        assert!(score("tr", "ts/trans") > score("tr", "ts/atrbs"));
+    }
+
+    #[test]
+    fn test_regex_match() {
+        let re = Regex::new("o+").unwrap();
+        assert_eq!(regex_match(&re, "foo_boo"), Some(vec![1, 2, 5, 6]));
+        // character indices, not byte indices
+        assert_eq!(regex_match(&re, "我foo"), Some(vec![2, 3]));
+        assert_eq!(regex_match(&re, "bar"), None);
+        assert_eq!(regex_match(&Regex::new("^").unwrap(), "bar"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_search_regex() {
+        let is_match = |search: &str, mode: SearchMode, text: &str| {
+            search_regex(&chars(search), mode).unwrap().is_match(text)
+        };
+        // smart case
+        assert!(is_match("readme", SearchMode::Regex, "README.md"));
+        assert!(!is_match("Readme", SearchMode::Regex, "README.md"));
+        assert!(!is_match("readme\\C", SearchMode::Regex, "README.md"));
+        assert!(is_match("Readme\\c", SearchMode::Regex, "README.md"));
+        // escapes aren't uppercase letters
+        assert!(is_match("\\Wrs", SearchMode::Regex, "MAIN.RS"));
+        // an escaped backslash doesn't make a flag
+        assert!(is_match("a\\\\C", SearchMode::Regex, "a\\C"));
+
+        assert!(is_match("a+b", SearchMode::Literal, "a+b.txt"));
+        assert!(!is_match("a+b", SearchMode::Literal, "aab.txt"));
+        assert!(is_match("*.rs", SearchMode::Glob, "main.rs"));
+        assert!(!is_match("*.rs", SearchMode::Glob, "main.rs.bak"));
+
+        // the prefix overrides the mode
+        assert!(is_match("\\Va+b", SearchMode::Regex, "a+b.txt"));
+        assert!(is_match("\\G*.rs", SearchMode::Literal, "main.rs"));
+        assert!(is_match("\\R^ma", SearchMode::Glob, "main.rs"));
+
+        assert!(search_regex(&chars("a("), SearchMode::Regex).is_err());
+        assert!(search_regex(&chars("a("), SearchMode::Literal).is_ok());
+    }
+
+    #[test]
+    fn test_score() {
+        let items = ["src/main.rs", "README.md", "src/matcher.rs"].map(String::from);
+        let indices = |pattern: &str, fuzzy: bool| {
+            score(&items, &chars(pattern), fuzzy)
+                .into_iter()
+                .map(|(_, i, _)| i)
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(indices("matcher", true), [2]);
+        assert_eq!(indices("mrs", true), [0, 2]);
+        assert_eq!(indices("", true), [0, 1, 2]);
+        assert_eq!(indices("^src", false), [0, 2]);
+        assert_eq!(indices("readme\\C", false), Vec::<usize>::new());
+        assert_eq!(indices("(", false), [0, 1, 2]);
+        // smart-case, fuzzy or regex
+        assert_eq!(indices("readme", false), [1]);
+        assert_eq!(indices("Readme", false), Vec::<usize>::new());
+        assert_eq!(indices("Readme", true), Vec::<usize>::new());
+        assert_eq!(indices("Readme\\c", false), [1]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let order = merge_order(&[30, 20, 20, 5], &[40, 20, 10]);
+        assert_eq!(order, [true, false, false, false, true, true, false]);
+        let mut ranked = vec!["b", "c", "d", "e"];
+        merge(&mut ranked, vec!["a", "c2", "d2"], &order);
+        assert_eq!(ranked, ["a", "b", "c", "d", "c2", "d2", "e"]);
+        assert_eq!(merge_order(&[0, 0], &[0]), [false, false, true]);
+    }
+}
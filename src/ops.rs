@@ -9,12 +9,24 @@
 /// All the constant
 pub mod consts {
     pub const HOME_VAR: &str = "HOME";
+    pub const SHELL_VAR: &str = "SHELL";
+    pub const STATE_HOME_VAR: &str = "XDG_STATE_HOME";
+    pub const STATE_DIR: &str = "transgender";
+    pub const BOOKMARK_FILE: &str = "bookmarks";
+    pub const JUMPLIST_FILE: &str = "jumplist";
+    pub const FRECENCY_FILE: &str = "frecency";
+    pub const TABS_FILE: &str = "tabs";
+    pub const SEARCH_HISTORY_FILE: &str = "search_history";
     pub const CONFIG_FILE: &str = ".tsrc";
     pub const EDITOR_KEY: &str = "editor";
     pub const OPENER_KEY: &str = "open";
     pub const OPENER: &str = "vi";
     pub const THEME_KEY: &str = "theme";
+    pub const SEARCH_KEY: &str = "search";
+    pub const SCROLLOFF_KEY: &str = "scrolloff";
+    pub const COLUMNS_KEY: &str = "columns";
     pub const O_KEY: &str = "o";
+    pub const LINE_PLACEHOLDER: &str = "{line}";
     pub const ENTER_KEY: &str = "enter";
 }
 
@@ -32,12 +44,60 @@ pub enum Op {
     Quit,
     Top,
     Bottom,
+    // H, M and L
+    ScreenTop,
+    ScreenMiddle,
+    ScreenBottom,
+    // zt, zz and zb
+    ScrollTop,
+    ScrollCenter,
+    ScrollBottom,
+    // Ctrl + E and Ctrl + Y
+    ScrollDown,
+    ScrollUp,
     Search,
     RevSearch,
     NextMatch,
     PrevMatch,
     PageUp,
     PageDown,
+    // Ctrl + B and Ctrl + F
+    FullPageUp,
+    FullPageDown,
+    // f<char>
+    FindChar(char),
+    Mark,
+    Delete,
+    SetBookmark(char),
+    GotoBookmark(char),
+    Bookmarks,
+    JumpBack,
+    JumpForward,
+    Frecency,
+    // gn, gc, gt and gT
+    NewTab,
+    CloseTab,
+    NextTab,
+    PrevTab,
+    // yy, dd and pp
+    Copy,
+    Cut,
+    Paste,
+    // t
+    Tree,
+    // R
+    Reload,
+    // Ctrl + W, Tab in the dual-pane mode, F5 and F6
+    DualPane,
+    SwitchPane,
+    CopyToOther,
+    MoveToOther,
+    Filter,
+    ClearFilter,
+    Find,
+    Grep,
+    // Esc
+    Cancel,
 }
 
 #[derive(Copy, Clone)]
@@ -45,4 +105,94 @@ pub enum Mode {
     Normal,
     Search,
     RevSearch,
+    Bookmarks,
+    Frecency,
+    Filter,
+    Find,
+    Grep,
+    GrepResults,
+}
+
+/// What is written to the --dest file
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DestFormat {
+    Lines,
+    Nul,
+    Json,
+}
+
+impl DestFormat {
+    pub fn from(name: &str) -> Option<DestFormat> {
+        match name {
+            "lines" => Some(DestFormat::Lines),
+            "nul" => Some(DestFormat::Nul),
+            "json" => Some(DestFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// How the search text is matched, '\R', '\V' or '\G' at the start of it overrides the mode
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SearchMode {
+    Regex,
+    Literal,
+    Glob,
+}
+
+impl SearchMode {
+    pub fn from(name: &str) -> Option<SearchMode> {
+        match name {
+            "regex" => Some(SearchMode::Regex),
+            "literal" => Some(SearchMode::Literal),
+            "glob" => Some(SearchMode::Glob),
+            _ => None,
+        }
+    }
+
+    /// The mode Ctrl + R switches to
+    pub fn next(&self) -> SearchMode {
+        match self {
+            SearchMode::Regex => SearchMode::Literal,
+            SearchMode::Literal => SearchMode::Glob,
+            SearchMode::Glob => SearchMode::Regex,
+        }
+    }
+}
+
+/// Why trans exited, each reason has its own exit code
+#[derive(Copy, Clone)]
+pub enum Exit {
+    // i, or <ENTER> on a directory
+    Cd,
+    // q, go back to where trans started
+    Quit,
+    // paths were picked in chooser mode
+    Choose,
+    // trans couldn't start, e.g. the path doesn't exist
+    Error,
+    // --query matched nothing
+    NoMatch,
+}
+
+impl Exit {
+    pub fn code(&self) -> i32 {
+        match self {
+            Exit::Cd => 0,
+            Exit::Quit => 1,
+            Exit::Choose => 2,
+            Exit::Error => 3,
+            Exit::NoMatch => 4,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Exit::Cd => "cd",
+            Exit::Quit => "quit",
+            Exit::Choose => "choose",
+            Exit::Error => "error",
+            Exit::NoMatch => "no match",
+        }
+    }
 }
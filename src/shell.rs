@@ -0,0 +1,179 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::ops::consts;
+use std::env::var;
+use std::path::Path;
+
+/// Shells that trans can generate the `ts` function for
+#[derive(PartialEq, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Nu,
+    Pwsh,
+    Posix,
+}
+
+// The destination file ends with a newline, a trailing 'x' keeps command substitution from
+// eating the newlines that belong to the directory name.
+static BASH_SCRIPT: &str = r###"function ts() {
+  local dest_file dest_dir
+  dest_file=$(mktemp "${TMPDIR:-/tmp}/ts.XXXXXX") || return
+  transgender "$@" --dest "${dest_file}"
+  dest_dir=$(cat "${dest_file}"; printf x)
+  dest_dir=${dest_dir%x}
+  dest_dir=${dest_dir%$'\n'}
+  rm -f "${dest_file}"
+  if [ -n "${dest_dir}" ]; then
+    cd -- "${dest_dir}"
+  fi
+}
+
+complete -o dirnames ts
+"###;
+
+static ZSH_SCRIPT: &str = r###"function ts() {
+  local dest_file dest_dir
+  dest_file=$(mktemp "${TMPDIR:-/tmp}/ts.XXXXXX") || return
+  transgender "$@" --dest "${dest_file}"
+  dest_dir=$(cat "${dest_file}"; printf x)
+  dest_dir=${dest_dir%x}
+  dest_dir=${dest_dir%$'\n'}
+  rm -f "${dest_file}"
+  if [ -n "${dest_dir}" ]; then
+    cd -- "${dest_dir}"
+  fi
+}
+
+autoload -U compinit
+compinit
+setopt globdots
+zstyle ':completion:*:*:ts:*' file-patterns '*(-/):directories'
+"###;
+
+static FISH_SCRIPT: &str = r###"function ts
+    set -l tmp_dir /tmp
+    set -q TMPDIR; and set tmp_dir $TMPDIR
+    set -l dest_file (mktemp $tmp_dir/ts.XXXXXX); or return
+    transgender $argv --dest $dest_file
+    set -l dest_dir (cat $dest_file | string collect)
+    rm -f $dest_file
+    if test -n "$dest_dir"
+        cd $dest_dir
+    end
+end
+
+complete -c ts -f -a '(__fish_complete_directories)'
+"###;
+
+static NU_SCRIPT: &str = r###"def "nu-complete ts" [] {
+  ls -a | where type == dir | get name
+}
+
+def --env --wrapped ts [...args: string@"nu-complete ts"] {
+  let dest_file = (mktemp -t ts.XXXXXX)
+  ^transgender ...$args --dest $dest_file
+  let dest_dir = (open --raw $dest_file | str trim --right --char "\n")
+  rm -f $dest_file
+  if $dest_dir != "" {
+    cd $dest_dir
+  }
+}
+"###;
+
+static PWSH_SCRIPT: &str = r###"function ts {
+  param([Parameter(ValueFromRemainingArguments)][string[]]$Rest)
+  $destFile = New-TemporaryFile
+  & transgender @Rest --dest $destFile.FullName
+  $destDir = [System.IO.File]::ReadAllText($destFile.FullName) -replace "`n$", ""
+  Remove-Item -Force $destFile
+  if ($destDir) {
+    Set-Location -LiteralPath $destDir
+  }
+}
+
+Register-ArgumentCompleter -CommandName ts -ParameterName Rest -ScriptBlock {
+  param($commandName, $parameterName, $wordToComplete, $commandAst, $fakeBoundParameters)
+  Get-ChildItem -Directory -Force -Path "$wordToComplete*" | ForEach-Object {
+    $dir = if ($_.FullName -match '\s') { "'$($_.FullName)'" } else { $_.FullName }
+    [System.Management.Automation.CompletionResult]::new($dir, $_.Name, 'ProviderContainer', $_.FullName)
+  }
+}
+"###;
+
+// POSIX sh has no completion, only the function
+static POSIX_SCRIPT: &str = r###"ts() {
+  ts_dest_file=$(mktemp "${TMPDIR:-/tmp}/ts.XXXXXX") || return
+  transgender "$@" --dest "${ts_dest_file}"
+  ts_dest_dir=$(cat "${ts_dest_file}"; printf x)
+  ts_dest_dir=${ts_dest_dir%x}
+  ts_dest_dir=${ts_dest_dir%"
+"}
+  rm -f "${ts_dest_file}"
+  if [ -n "${ts_dest_dir}" ]; then
+    cd -- "${ts_dest_dir}"
+  fi
+  unset ts_dest_file ts_dest_dir
+}
+"###;
+
+impl Shell {
+    /// Get the shell from its name or path, e.g. zsh or /usr/bin/zsh
+    pub fn from(name: &str) -> Option<Shell> {
+        let name = Path::new(name).file_name()?.to_str()?;
+        match name.to_lowercase().trim_end_matches(".exe") {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "nu" | "nushell" => Some(Shell::Nu),
+            "pwsh" | "powershell" => Some(Shell::Pwsh),
+            "sh" | "dash" | "ash" | "ksh" | "mksh" | "posix" => Some(Shell::Posix),
+            _ => None,
+        }
+    }
+
+    /// Guess the user's shell from $SHELL, bash if it's unknown
+    pub fn detect() -> Shell {
+        match var(consts::SHELL_VAR) {
+            Ok(shell) => Shell::from(&shell).unwrap_or(Shell::Bash),
+            Err(_) => Shell::Bash,
+        }
+    }
+
+    /// The `ts` wrapper function and directory completion for this shell
+    pub fn script(&self) -> &'static str {
+        match self {
+            Shell::Bash => BASH_SCRIPT,
+            Shell::Zsh => ZSH_SCRIPT,
+            Shell::Fish => FISH_SCRIPT,
+            Shell::Nu => NU_SCRIPT,
+            Shell::Pwsh => PWSH_SCRIPT,
+            Shell::Posix => POSIX_SCRIPT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shell_from() {
+        assert_eq!(Shell::from("bash"), Some(Shell::Bash));
+        assert_eq!(Shell::from("/usr/bin/zsh"), Some(Shell::Zsh));
+        assert_eq!(Shell::from("/opt/homebrew/bin/fish"), Some(Shell::Fish));
+        assert_eq!(Shell::from("nu"), Some(Shell::Nu));
+        assert_eq!(Shell::from("/usr/bin/pwsh"), Some(Shell::Pwsh));
+        assert_eq!(Shell::from("/bin/dash"), Some(Shell::Posix));
+        assert_eq!(Shell::from("/bin/sh"), Some(Shell::Posix));
+        assert_eq!(Shell::from("tcsh"), None);
+        assert_eq!(Shell::from(""), None);
+    }
+}
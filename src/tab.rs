@@ -0,0 +1,235 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::browser::Filter;
+use crate::jumplist::Jump;
+use crate::load::Loader;
+use crate::util;
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+
+/// An entry of the listing. In the tree view the listing is a flattened tree, where an entry of
+/// an expanded directory follows it with a path relative to the tab's directory and a depth one
+/// more than the directory's.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Entry {
+    pub path: Box<str>,
+    pub depth: usize,
+}
+
+impl Entry {
+    /// The file name, which is what's drawn, searched and filtered
+    pub fn name(&self) -> &str {
+        match self.path.rsplit_once('/') {
+            Some((_, name)) => name,
+            None => &self.path,
+        }
+    }
+
+    /// The expanded directory the entry is in, None at the top of the tree
+    pub fn parent(&self) -> Option<&str> {
+        self.path.rsplit_once('/').map(|(parent, _)| parent)
+    }
+}
+
+impl From<String> for Entry {
+    fn from(path: String) -> Self {
+        Entry {
+            path: path.into_boxed_str(),
+            depth: 0,
+        }
+    }
+}
+
+impl From<&str> for Entry {
+    fn from(path: &str) -> Self {
+        Entry::from(path.to_string())
+    }
+}
+
+impl From<Box<str>> for Entry {
+    fn from(path: Box<str>) -> Self {
+        Entry { path, depth: 0 }
+    }
+}
+
+/// What a tab shows: a directory, where the cursor is in it, how it got there and how it's
+/// listed. Everything else, the prompts, the bookmarks and what's copied, is shared by the tabs.
+#[derive(Default)]
+pub struct Tab {
+    pub cursor: usize,
+    pub window_start: usize,
+    pub content: Vec<Entry>,
+    pub past_dir: Vec<PathBuf>,
+    pub past_cursor: Vec<usize>,
+    pub past_window_start: Vec<usize>,
+    pub current_path: PathBuf,
+    pub preview: Vec<String>,
+    // there's more in the directory than what's read for the preview
+    pub preview_truncated: bool,
+    // the directory the preview lists, None while it's being read or for a file
+    pub preview_dir: Option<PathBuf>,
+    pub refresh_preview: bool,
+    // the parent directory for the parent column, and where the current directory is in it
+    pub parent: Vec<String>,
+    pub parent_cursor: Option<usize>,
+    // the directory parent was read for
+    pub parent_of: Option<PathBuf>,
+    pub marked: Vec<PathBuf>,
+    pub filter_txt: Vec<char>,
+    pub filter: Option<Filter>,
+    // the tree view, and the directories expanded in it
+    pub tree: bool,
+    pub expanded: HashSet<PathBuf>,
+    // indices of the entries matching the search, None once the content or the search changed
+    pub search_matches: Option<Vec<usize>>,
+    // the directory being read, a huge one is listed as it's read
+    pub loader: Option<Loader>,
+}
+
+impl Tab {
+    /// The name of the entry under the cursor, empty in an empty directory
+    pub fn cursor_name(&self) -> String {
+        match self.content.get(self.cursor) {
+            Some(entry) => entry.path.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// What's drawn before the name of each entry in the tree view, the guides of the directories
+    /// the entry is in. Empty outside of the tree view.
+    pub fn tree_guides(&self) -> Vec<String> {
+        if !self.tree {
+            return Vec::new();
+        }
+        // going up from the bottom, whether there's another entry further down at each depth
+        let mut more: Vec<bool> = Vec::new();
+        let mut guides = vec![String::new(); self.content.len()];
+        for (i, entry) in self.content.iter().enumerate().rev() {
+            more.resize(entry.depth + 1, false);
+            if entry.depth > 0 {
+                for below in &more[1..entry.depth] {
+                    guides[i].push_str(if *below { "│  " } else { "   " });
+                }
+                guides[i].push_str(if more[entry.depth] {
+                    "├─ "
+                } else {
+                    "└─ "
+                });
+            }
+            more[entry.depth] = true;
+        }
+        guides
+    }
+
+    /// Where the tab is, to be opened again by --last
+    pub fn jump(&self) -> Jump {
+        Jump {
+            path: self.current_path.clone(),
+            cursor: self.cursor_name(),
+        }
+    }
+}
+
+/// Save the tabs of this session as "<cursor>\t<path>" lines escaped like the jumplist, after a
+/// line with the index of the current tab
+pub fn save_tabs(file: Option<PathBuf>, tabs: &[Jump], current: usize) -> io::Result<()> {
+    let file = match file {
+        Some(file) => file,
+        None => return Ok(()),
+    };
+    let mut content = format!("{}\n", current);
+    for tab in tabs {
+        content.push_str(&format!(
+            "{}\t{}\n",
+            util::escape(&tab.cursor),
+            util::escape(&tab.path.to_string_lossy())
+        ));
+    }
+    util::write_atomic(&file, content.as_bytes())
+}
+
+/// The tabs of the last session and which one was the current one
+pub fn load_tabs(file: Option<PathBuf>) -> (Vec<Jump>, usize) {
+    let content = match file {
+        Some(file) => read_to_string(file).unwrap_or_default(),
+        None => String::new(),
+    };
+    let mut lines = content.lines();
+    let current = lines.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+    let tabs = lines
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(cursor, path)| Jump {
+            path: PathBuf::from(util::unescape(path)),
+            cursor: util::unescape(cursor),
+        })
+        .collect::<Vec<Jump>>();
+    (tabs, current)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::random_dir_wcontent;
+
+    #[test]
+    fn test_save_tabs() {
+        let (_, _, root_dir, _cd) = random_dir_wcontent();
+        let file = PathBuf::from(format!("/tmp/{}/state/tabs", root_dir));
+        assert!(load_tabs(Some(file.clone())).0.is_empty());
+        let tabs = [
+            Jump {
+                path: PathBuf::from("/a"),
+                cursor: "x".to_string(),
+            },
+            Jump {
+                path: PathBuf::from("/b c"),
+                cursor: String::new(),
+            },
+            // names with tabs and newlines stay on their line
+            Jump {
+                path: PathBuf::from("/d\te\nf"),
+                cursor: "g\th\n\\".to_string(),
+            },
+        ];
+        save_tabs(Some(file.clone()), &tabs, 2).expect("failed to save tabs");
+        assert_eq!(load_tabs(Some(file)), (tabs.to_vec(), 2));
+    }
+
+    #[test]
+    fn test_tree_guides() {
+        let entry = |path: &str, depth| Entry {
+            path: path.into(),
+            depth,
+        };
+        let mut tab = Tab {
+            content: vec![
+                entry("a", 0),
+                entry("a/b", 1),
+                entry("a/b/c", 2),
+                entry("a/d", 1),
+                entry("a/d/e", 2),
+                entry("f", 0),
+            ],
+            ..Tab::default()
+        };
+        assert!(tab.tree_guides().is_empty());
+        tab.tree = true;
+        assert_eq!(
+            tab.tree_guides(),
+            ["", "├─ ", "│  └─ ", "└─ ", "   └─ ", ""]
+        );
+        assert_eq!(
+            (tab.content[2].name(), tab.content[2].parent()),
+            ("c", Some("a/b"))
+        );
+        assert_eq!(tab.content[5].parent(), None);
+    }
+}
@@ -18,6 +18,10 @@ pub struct Theme {
     pub normal: String,
     // background of everything
     pub normal_background: String,
+    // entry names marked with space
+    pub marked: String,
+    // entry names matching the search
+    pub search_match: String,
     // these two are optional, if not specified, bottom_bar uses normal, and bottom_bar_background
     // uses normal_background
     pub bottom_bar: String,
@@ -66,6 +70,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;5;24m".to_string(),
                         normal:                "\x1b[0;37m".to_string(),
                         normal_background:     "\x1b[48;5;31m".to_string(),
+                        marked:                "\x1b[38;5;226m".to_string(),
+                        search_match:          "\x1b[38;5;218m".to_string(),
                         bottom_bar:            "\x1b[38;5;0m".to_string(),
                         bottom_bar_background: "\x1b[48;5;175m".to_string()
                     }
@@ -78,6 +84,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;5;255m".to_string(),
                         normal:                "\x1b[38;5;255m".to_string(),
                         normal_background:     "\x1b[48;5;0m".to_string(),
+                        marked:                "\x1b[38;5;214m".to_string(),
+                        search_match:          "\x1b[38;5;81m".to_string(),
                         bottom_bar:            "\x1b[38;5;255m".to_string(),
                         bottom_bar_background: "\x1b[38;5;255m".to_string()
                     }
@@ -90,6 +98,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;5;238m".to_string(),
                         normal:                "\x1b[38;5;188m".to_string(),
                         normal_background:     "\x1b[48;5;236m".to_string(),
+                        marked:                "\x1b[38;5;180m".to_string(),
+                        search_match:          "\x1b[38;5;150m".to_string(),
                         bottom_bar:            "\x1b[38;5;188m".to_string(),
                         bottom_bar_background: "\x1b[48;5;238m".to_string()
                     }
@@ -102,6 +112,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;5;186m".to_string(),
                         normal:                "\x1b[38;5;233m".to_string(),
                         normal_background:     "\x1b[48;5;230m".to_string(),
+                        marked:                "\x1b[38;5;130m".to_string(),
+                        search_match:          "\x1b[38;5;28m".to_string(),
                         bottom_bar:            "\x1b[38;5;233m".to_string(),
                         bottom_bar_background: "\x1b[48;5;195m".to_string()
                     }
@@ -114,6 +126,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;5;175m".to_string(),
                         normal:                "\x1b[38;5;253m".to_string(),
                         normal_background:     "\x1b[48;5;168m".to_string(),
+                        marked:                "\x1b[38;5;226m".to_string(),
+                        search_match:          "\x1b[38;5;230m".to_string(),
                         bottom_bar:            "\x1b[38;5;52m".to_string(),
                         bottom_bar_background: "\x1b[48;5;175m".to_string()
                     }
@@ -126,6 +140,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;5;236m".to_string(),
                         normal:                "\x1b[38;5;75m".to_string(),
                         normal_background:     "\x1b[48;5;235m".to_string(),
+                        marked:                "\x1b[38;5;222m".to_string(),
+                        search_match:          "\x1b[38;5;180m".to_string(),
                         bottom_bar:            "\x1b[38;5;117m".to_string(),
                         bottom_bar_background: "\x1b[48;5;236m".to_string()
                     }
@@ -138,6 +154,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;5;180m".to_string(),
                         normal:                "\x1b[38;5;187m".to_string(),
                         normal_background:     "\x1b[48;5;137m".to_string(),
+                        marked:                "\x1b[38;5;52m".to_string(),
+                        search_match:          "\x1b[38;5;229m".to_string(),
                         bottom_bar:            "\x1b[38;5;236m".to_string(),
                         bottom_bar_background: "\x1b[48;5;180m".to_string()
                     }
@@ -150,6 +168,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;2;48;51;71m".to_string(),
                         normal:                "\x1b[38;2;138;173;244m".to_string(),
                         normal_background:     "\x1b[48;2;36;39;58m".to_string(),
+                        marked:                "\x1b[38;2;166;218;149m".to_string(),
+                        search_match:          "\x1b[38;2;245;189;230m".to_string(),
                         bottom_bar:            "\x1b[38;2;128;135;162m".to_string(),
                         bottom_bar_background: "\x1b[48;2;54;58;79m".to_string()
                     }
@@ -162,6 +182,8 @@ impl ThemeTable {
                         highlight_background:  "\x1b[48;5;253m".to_string(),
                         normal:                "\x1b[38;5;238m".to_string(),
                         normal_background:     "\x1b[48;5;255m".to_string(),
+                        marked:                "\x1b[38;5;166m".to_string(),
+                        search_match:          "\x1b[38;5;28m".to_string(),
                         bottom_bar:            "\x1b[38;5;255m".to_string(),
                         bottom_bar_background: "\x1b[48;5;244m".to_string()
                     }
@@ -193,6 +215,8 @@ mod test {
             assert_eq!(got.highlight_background,  need.highlight_background);
             assert_eq!(got.normal,                need.normal);
             assert_eq!(got.normal_background,     need.normal_background);
+            assert_eq!(got.marked,                need.marked);
+            assert_eq!(got.search_match,          need.search_match);
             assert_eq!(got.bottom_bar,            need.bottom_bar);
             assert_eq!(got.bottom_bar_background, need.bottom_bar_background);
         } else {
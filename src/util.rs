@@ -9,18 +9,28 @@
 extern crate libc;
 
 use self::libc::{
-    c_ushort, ioctl, tcgetattr, tcsetattr, termios, ECHO, ICANON, ISIG, STDIN_FILENO,
-    STDOUT_FILENO, TCSAFLUSH, TIOCGWINSZ,
+    c_ushort, dup, dup2, flock, ioctl, isatty, poll, pollfd, setsid, tcgetattr, tcsetattr, termios,
+    ECHO, ICANON, ISIG, LOCK_EX, POLLIN, STDIN_FILENO, STDOUT_FILENO, TCSAFLUSH, TIOCGWINSZ,
 };
-use crate::ops::{consts, Op};
+use crate::input::Key;
+use crate::ops::{consts, Op, SearchMode};
 use std::env::var;
 use std::ffi::OsString;
-use std::fs::File;
-use std::io::{self, stdin, BufRead, Read, Write};
+use std::fs::{
+    create_dir, create_dir_all, read_dir, read_link, remove_dir_all, remove_file, rename,
+    set_permissions, symlink_metadata, File, OpenOptions,
+};
+use std::io::{self, BufRead, Read, Write};
 use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::symlink;
+use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
 use std::str::from_utf8;
-use std::thread::sleep;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, sleep};
 use std::time::Duration;
 use std::vec::Vec;
 
@@ -90,77 +100,168 @@ pub fn enter_albuf() {
     raw_input();
     hide_cursor();
     print!("\x1b[?1049h"); // use alternate buffer
+    print!("\x1b[?2004h"); // bracketed paste, so a pasted line break isn't an Enter
+    print!("\x1b[>1u"); // kitty keyboard protocol, Esc and the Ctrl/Alt keys are unambiguous
+    print!("\x1b[?1000h\x1b[?1006h"); // mouse clicks and wheel, SGR encoded
     let _ = io::stdout().flush();
 }
 
 pub fn exit_albuf() {
     canonical_input();
     show_cursor();
+    print!("\x1b[?1006l\x1b[?1000l");
+    print!("\x1b[<u");
+    print!("\x1b[?2004l");
     print!("\x1b[?1049l"); // switch back to normal screen buffer
     let _ = io::stdout().flush();
 }
 
-/// Read a single ascii byte input
+/// Spawn a program in its own session with stdio redirected to /dev/null, so it neither draws
+/// over trans nor dies with the terminal. The child is waited on by a detached thread so it
+/// doesn't linger as a zombie.
+pub fn spawn_detached(comm: &OsString, args: &[OsString], file: &Path) -> io::Result<()> {
+    let mut command = Command::new(comm);
+    command
+        .args(args)
+        .arg(file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    unsafe {
+        command.pre_exec(|| {
+            setsid();
+            Ok(())
+        });
+    }
+    let mut child = command.spawn()?;
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+/// Wait for input on the file descriptor, stdin for keys, for at most timeout milliseconds, -1
+/// waits forever
 ///
 /// returns
-///  ascii byte
-fn read_input() -> isize {
-    let mut stdin_handle = stdin().lock();
-    let mut byte = [0_u8];
-    stdin_handle
-        .read_exact(&mut byte)
-        .expect("Failed to read single byte");
-    byte[0] as isize
-}
-
-pub fn process_input() -> Op {
-    let mut input = read_input();
-
-    if input == 27 {
-        // arrow keys
-        match read_input() {
-            91 => match read_input() {
-                65 => return Op::Up,
-                66 => return Op::Down,
-                67 => return Op::Right,
-                68 => return Op::Left,
-                _ => return Op::Noop,
-            },
-            _ => return Op::Noop,
-        }
-    }
+///  whether there's input to read
+pub fn fd_ready(fd: i32, timeout: i32) -> bool {
+    let mut fds = pollfd {
+        fd,
+        events: POLLIN,
+        revents: 0,
+    };
+    unsafe { poll(&mut fds, 1, timeout) > 0 }
+}
+
+/// What's typed before the key of a command: a count, e.g. 5 of 5j, and the first key of a two
+/// key command, e.g. g of gg
+#[derive(Default)]
+pub struct Pending {
+    count: Option<usize>,
+    prefix: Option<char>,
+}
 
-    if input == 4 {
-        // ctrl + D
-        return Op::PageDown;
-    } else if input == 21 {
-        // ctrl + U
-        return Op::PageUp;
+/// Map a key in normal mode to an operation. A count can come first, and g, m, ', z, f, y, d and p
+/// start two key commands (gg, gm, m<letter>, '<letter>, zt/zz/zb, f<char>, yy, dd and pp), what's
+/// typed so far is kept in pending until the command is complete.
+///
+/// returns
+///  the operation and its count, None if there isn't one
+pub fn process_input(key: Key, pending: &mut Pending) -> (Op, Option<usize>) {
+    match key {
+        // 0 isn't a count on its own
+        Key::Char(c @ '0'..='9')
+            if pending.prefix.is_none() && (c != '0' || pending.count.is_some()) =>
+        {
+            let digit = c as usize - '0' as usize;
+            let count = pending.count.unwrap_or(0);
+            pending.count = Some(count.saturating_mul(10).saturating_add(digit));
+            (Op::Noop, None)
+        }
+        key => {
+            let op = key_op(key, &mut pending.prefix);
+            if pending.prefix.is_some() {
+                // the count is for the command the prefix starts
+                return (op, None);
+            }
+            (op, pending.count.take())
+        }
     }
+}
 
-    // gg
-    if input == 103 {
-        input = read_input();
-        if input == 103 {
-            return Op::Top;
+fn key_op(key: Key, prefix: &mut Option<char>) -> Op {
+    if let Some(first) = prefix.take() {
+        match (first, &key) {
+            ('g', Key::Char('g')) => return Op::Top,
+            ('g', Key::Char('n')) => return Op::NewTab,
+            ('g', Key::Char('c')) => return Op::CloseTab,
+            ('g', Key::Char('t')) => return Op::NextTab,
+            ('g', Key::Char('T')) => return Op::PrevTab,
+            // anything else after g is a key on its own
+            ('g', _) => {}
+            ('m', Key::Char(letter)) if letter.is_ascii_alphabetic() => {
+                return Op::SetBookmark(*letter)
+            }
+            ('\'', Key::Char(letter)) if letter.is_ascii_alphabetic() => {
+                return Op::GotoBookmark(*letter)
+            }
+            ('z', Key::Char('t')) => return Op::ScrollTop,
+            ('z', Key::Char('z')) => return Op::ScrollCenter,
+            ('z', Key::Char('b')) => return Op::ScrollBottom,
+            ('f', Key::Char(c)) => return Op::FindChar(*c),
+            ('y', Key::Char('y')) => return Op::Copy,
+            ('d', Key::Char('d')) => return Op::Cut,
+            ('p', Key::Char('p')) => return Op::Paste,
+            _ => return Op::Noop,
         }
     }
 
-    match input {
-        107 => return Op::Up,             // k
-        106 => return Op::Down,           // j
-        104 => return Op::Left,           // h
-        108 => return Op::Right,          // l
-        111 => return Op::ExitCursorO,    // o
-        10 => return Op::ExitCursorEnter, // Enter
-        105 => return Op::Exit,           // i
-        113 => return Op::Quit,           // q
-        47 => return Op::Search,          // /
-        63 => return Op::RevSearch,       // ?
-        71 => return Op::Bottom,          // G
-        110 => return Op::NextMatch,      // n
-        78 => return Op::PrevMatch,       // N
-        _ => return Op::Noop,
+    match key {
+        Key::Char(c @ ('g' | 'm' | '\'' | 'z' | 'f' | 'y' | 'd' | 'p')) => {
+            *prefix = Some(c);
+            Op::Noop
+        }
+        Key::Up | Key::Char('k') => Op::Up,
+        Key::Down | Key::Char('j') => Op::Down,
+        Key::Left | Key::Char('h') => Op::Left,
+        Key::Right | Key::Char('l') => Op::Right,
+        Key::Home => Op::Top,
+        Key::End | Key::Char('G') => Op::Bottom,
+        Key::Char('H') => Op::ScreenTop,
+        Key::Char('M') => Op::ScreenMiddle,
+        Key::Char('L') => Op::ScreenBottom,
+        Key::Char('Z') => Op::Frecency,
+        Key::PageDown | Key::Ctrl('d') => Op::PageDown,
+        Key::PageUp | Key::Ctrl('u') => Op::PageUp,
+        Key::Ctrl('f') => Op::FullPageDown,
+        Key::Ctrl('b') => Op::FullPageUp,
+        Key::Ctrl('e') => Op::ScrollDown,
+        Key::Ctrl('y') => Op::ScrollUp,
+        Key::Ctrl('o') => Op::JumpBack,
+        // Ctrl + I is Tab, which switches the panes in the dual-pane mode, Ctrl + N works there too
+        Key::Tab | Key::Ctrl('n') => Op::JumpForward,
+        Key::Ctrl('g') => Op::Grep,
+        Key::Ctrl('t') => Op::Find,
+        Key::Ctrl('w') => Op::DualPane,
+        Key::F(5) => Op::CopyToOther,
+        Key::F(6) => Op::MoveToOther,
+        Key::Char('o') => Op::ExitCursorO,
+        Key::Enter => Op::ExitCursorEnter,
+        Key::Char('i') => Op::Exit,
+        Key::Char('q') => Op::Quit,
+        Key::Esc => Op::Cancel,
+        Key::Char('/') => Op::Search,
+        Key::Char('?') => Op::RevSearch,
+        Key::Char(' ') => Op::Mark,
+        Key::Char('n') => Op::NextMatch,
+        Key::Char('N') => Op::PrevMatch,
+        Key::Char('t') => Op::Tree,
+        Key::Char('R') => Op::Reload,
+        Key::Char('`') => Op::Bookmarks,
+        Key::Char('F') => Op::Filter,
+        Key::Backspace => Op::ClearFilter,
+        _ => Op::Noop,
     }
 }
 
@@ -176,6 +277,15 @@ where
 /// consume.
 pub fn print_path(_path: &PathBuf, dest_file: Option<&PathBuf>) {
     let path = String::from(_path.to_str().expect("Failed to output file path")) + "\n";
+    if dest_file.is_some() {
+        print_dest(path.as_bytes(), dest_file);
+    } else {
+        println!("\n{}", path);
+    }
+}
+
+/// Write the exit output as is to the destination file, or stdout if there isn't one
+pub fn print_dest(content: &[u8], dest_file: Option<&PathBuf>) {
     if dest_file.is_some() {
         let mut file = File::create(dest_file.unwrap()).expect(&format!(
             "Failed to write to temporary destination file {}",
@@ -184,102 +294,323 @@ pub fn print_path(_path: &PathBuf, dest_file: Option<&PathBuf>) {
                 .to_str()
                 .expect("Failed to print the temporary destination file")
         ));
-        file.write_all(path.as_bytes()).expect("failed to write all");
+        file.write_all(content).expect("failed to write all");
         file.flush().expect("failed to flush");
     } else {
-        println!("\n{}", path);
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(content);
+        let _ = stdout.flush();
     }
 }
 
-pub fn get_theme(_config_path: Option<&str>) -> String {
-    let config_path = if _config_path.is_some() {
-        _config_path.unwrap().to_string()
-    } else {
-        let home_dir = var(consts::HOME_VAR).expect("failed to get HOME env");
-        format!("{}/{}", home_dir, consts::CONFIG_FILE)
+/// Quote a string as a JSON string
+pub fn json_str(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Keep whatever stdout is (usually a pipe, e.g. `vim $(transgender --choose-files -)`) for the
+/// chosen paths, and draw trans on the terminal instead.
+///
+/// returns
+///  the original stdout
+pub fn stdout_to_tty() -> io::Result<File> {
+    unsafe {
+        let out = dup(STDOUT_FILENO);
+        if out < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let out = File::from_raw_fd(out);
+        if isatty(out.as_raw_fd()) == 0 {
+            let tty = OpenOptions::new().write(true).open("/dev/tty")?;
+            if dup2(tty.as_raw_fd(), STDOUT_FILENO) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Write paths one per line, or NUL-terminated so that any file name is safe
+pub fn write_paths(out: &mut impl Write, paths: &[PathBuf], nul: bool) -> io::Result<()> {
+    for path in paths {
+        out.write_all(path.as_os_str().as_bytes())?;
+        out.write_all(if nul { b"\0" } else { b"\n" })?;
+    }
+    out.flush()
+}
+
+/// Directory for the files trans keeps between runs, $XDG_STATE_HOME/transgender
+///
+/// returns
+///  None when there's no place for it, and always in tests so they don't touch the user's state
+#[allow(unreachable_code)]
+pub fn state_dir() -> Option<PathBuf> {
+    #[cfg(test)]
+    {
+        return None;
+    }
+    let mut dir = match var(consts::STATE_HOME_VAR) {
+        Ok(state_home) if !state_home.is_empty() => PathBuf::from(state_home),
+        _ => {
+            let mut home = PathBuf::from(var(consts::HOME_VAR).ok()?);
+            home.push(".local/state");
+            home
+        }
     };
-    if let Ok(lines) = read_lines(&config_path) {
-        for line in lines.flatten() {
-            let kv = line.split("=").collect::<Vec<&str>>();
-            if kv.len() != 2 {
+    dir.push(consts::STATE_DIR);
+    Some(dir)
+}
+
+/// A file in the state directory
+pub fn state_file(name: &str) -> Option<PathBuf> {
+    let mut file = state_dir()?;
+    file.push(name);
+    Some(file)
+}
+
+/// A name or a path for a line of a state file, backslashes, newlines and tabs are escaped as
+/// \\, \n and \t so it stays on its line and a tab can separate it from other fields
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+/// The text escape was given
+pub fn unescape(line: &str) -> String {
+    let mut text = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => text.push('\n'),
+            ('\\', Some('t')) => text.push('\t'),
+            ('\\', Some('\\')) => text.push('\\'),
+            _ => {
+                text.push(c);
                 continue;
             }
-            if kv[0].trim().to_lowercase() == consts::THEME_KEY {
-                return kv[1].trim().to_lowercase();
+        }
+        chars.next();
+    }
+    text
+}
+
+/// Replace the file with new content, a reader sees either the old or the new file, never a half
+/// written one
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", process::id()));
+    let mut file = File::create(&tmp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    rename(&tmp, path)
+}
+
+/// Lock the file for a read, merge and write of it, another trans waits until it's unlocked. The
+/// lock is on a '.lock' file next to it, since write_atomic replaces the file. It's unlocked when
+/// the returned file is dropped.
+pub fn lock(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    loop {
+        if unsafe { flock(file.as_raw_fd(), LOCK_EX) } == 0 {
+            return Ok(file);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
+
+/// Copy a file a chunk at a time, so a big one can be cancelled half way
+fn copy_file(from: &Path, to: &Path, cancel: &AtomicBool) -> io::Result<()> {
+    let mut src = File::open(from)?;
+    let mut dest = File::create(to)?;
+    let mut buf = vec![0_u8; 1 << 20];
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(cancelled());
+        }
+        let len = src.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        dest.write_all(&buf[..len])?;
+    }
+    set_permissions(to, src.metadata()?.permissions())
+}
+
+/// Copy a file, a symlink or a directory with everything in it. It stops with an Interrupted
+/// error when cancel is set, what's copied so far is left.
+pub fn copy_path(from: &Path, to: &Path, cancel: &AtomicBool) -> io::Result<()> {
+    if cancel.load(Ordering::Relaxed) {
+        return Err(cancelled());
+    }
+    if to.starts_with(from) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "can't copy a directory into itself",
+        ));
+    }
+    let meta = symlink_metadata(from)?;
+    if meta.file_type().is_symlink() {
+        symlink(read_link(from)?, to)
+    } else if meta.is_dir() {
+        create_dir(to)?;
+        for entry in read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()), cancel)?;
+        }
+        Ok(())
+    } else {
+        copy_file(from, to, cancel)
+    }
+}
+
+/// Move a file or a directory, it's copied and removed when it's on another file system. A
+/// cancelled copy leaves the original where it was.
+pub fn move_path(from: &Path, to: &Path, cancel: &AtomicBool) -> io::Result<()> {
+    match rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            copy_path(from, to, cancel)?;
+            remove_path(from)
+        }
+        result => result,
+    }
+}
+
+/// Remove a file, a symlink or a directory with everything in it
+pub fn remove_path(path: &Path) -> io::Result<()> {
+    if symlink_metadata(path)?.is_dir() {
+        remove_dir_all(path)
+    } else {
+        remove_file(path)
+    }
+}
+
+/// Read the value of a key in trans config file
+pub fn get_config(key: &str, _config_path: Option<&str>) -> Option<String> {
+    let config_path = match _config_path {
+        Some(config_path) => config_path.to_string(),
+        None => {
+            let home_dir = var(consts::HOME_VAR).expect("failed to get HOME env");
+            format!("{}/{}", home_dir, consts::CONFIG_FILE)
+        }
+    };
+    let lines = read_lines(&config_path).ok()?;
+    for line in lines.map_while(Result::ok) {
+        if let Some((k, v)) = line.split_once('=') {
+            if k.trim().to_lowercase() == key {
+                return Some(v.trim().to_string());
             }
         }
     }
-    return String::new();
+    None
+}
+
+/// Lines kept above and below the cursor when scrolling, 'scrolloff = <lines>' in the config
+/// file, 0 by default
+pub fn get_scrolloff(config_path: Option<&str>) -> usize {
+    get_config(consts::SCROLLOFF_KEY, config_path)
+        .and_then(|lines| lines.parse().ok())
+        .unwrap_or(0)
 }
 
-/// Read trans config file to get preferred opener
+/// The ratios of the column widths, 'columns = 1,3,4' in the config file for the parent, the
+/// current directory and the preview. With two ratios there's no parent column, with one there's
+/// only the current directory. 6,4 by default.
+pub fn get_columns(config_path: Option<&str>) -> Vec<usize> {
+    get_config(consts::COLUMNS_KEY, config_path)
+        .and_then(|columns| {
+            columns
+                .split(',')
+                .map(|ratio| {
+                    ratio
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|ratio| *ratio > 0)
+                })
+                .collect::<Option<Vec<usize>>>()
+        })
+        .filter(|ratios| (1..=3).contains(&ratios.len()))
+        .unwrap_or(vec![6, 4])
+}
+
+/// How the search text is matched, 'search = literal' or 'search = glob' in the config file, a
+/// regex otherwise
+pub fn get_search_mode(config_path: Option<&str>) -> SearchMode {
+    get_config(consts::SEARCH_KEY, config_path)
+        .and_then(|mode| SearchMode::from(&mode))
+        .unwrap_or(SearchMode::Regex)
+}
+
+/// The theme's name, 'theme = <name>' in the config file, empty for the default one
+pub fn get_theme(config_path: Option<&str>) -> String {
+    get_config(consts::THEME_KEY, config_path)
+        .map(|theme| theme.to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Read trans config file to get preferred opener, 'o = ' or 'enter = ' for the key, otherwise
+/// 'editor = ' or 'open = '
 ///
 /// returns
 ///  opener's command with arguments
-pub fn get_opener(op: Op, _config_path: Option<&str>) -> (OsString, Option<Vec<OsString>>) {
+pub fn get_opener(op: Op, config_path: Option<&str>) -> (OsString, Option<Vec<OsString>>) {
     let key = match op {
         Op::ExitCursorO => Some(consts::O_KEY),
         Op::ExitCursorEnter => Some(consts::ENTER_KEY),
         _ => None,
     };
-    let config_path = if _config_path.is_some() {
-        _config_path.unwrap().to_string()
-    } else {
-        let home_dir = var(consts::HOME_VAR).expect("failed to get HOME env");
-        format!("{}/{}", home_dir, consts::CONFIG_FILE)
-    };
-    let mut comm = OsString::from(consts::OPENER);
-    let mut args: Option<Vec<OsString>> = None;
-    if let Ok(lines) = read_lines(&config_path) {
-        for line in lines.flatten() {
-            let kv = line.split("=").collect::<Vec<&str>>();
-            if kv.len() != 2 {
-                continue;
-            }
-            if key.is_some() {
-                if kv[0].trim().to_lowercase() == key.unwrap() {
-                    // "key =.*"
-                    let comm_op = kv[1].trim().split(" ").collect::<Vec<&str>>();
-                    if comm_op.len() != 0 {
-                        // key = code.*
-                        comm = OsString::from(comm_op[0]);
-                        args = Some(
-                            comm_op
-                                .into_iter()
-                                .skip(1)
-                                .map(|x| OsString::from(x))
-                                .collect(),
-                        );
-                        return (comm, args);
-                    }
-                }
-            }
-            if kv[0].trim().to_lowercase() == consts::EDITOR_KEY
-                || kv[0].trim().to_lowercase() == consts::OPENER_KEY
-            {
-                let comm_op = kv[1].trim().split(" ").collect::<Vec<&str>>();
-                if comm_op.len() != 0 {
-                    // can be overridden by 'o' or 'enter'
-                    comm = OsString::from(comm_op[0]);
-                    args = Some(
-                        comm_op
-                            .into_iter()
-                            .skip(1)
-                            .map(|x| OsString::from(x))
-                            .collect(),
-                    );
-                }
-            }
+    let command = key
+        .and_then(|key| get_config(key, config_path))
+        .or_else(|| get_config(consts::EDITOR_KEY, config_path))
+        .or_else(|| get_config(consts::OPENER_KEY, config_path));
+    match command {
+        Some(command) => {
+            let mut words = command.split(' ').map(OsString::from);
+            let comm = words.next().unwrap_or_default();
+            (comm, Some(words.collect()))
         }
+        None => (OsString::from(consts::OPENER), None),
     }
-    (comm, args)
 }
 
 /// Parse a byte array to a vector of chars
 ///
 /// returns
 ///  the parsed char array along with trailing truncated bytes for the next parsing
-fn parse_utf8(_raw: &[u8], prev_trunc: &Vec<u8>) -> (Vec<char>, Vec<u8>) {
+pub fn parse_utf8(_raw: &[u8], prev_trunc: &Vec<u8>) -> (Vec<char>, Vec<u8>) {
     let mut res: Vec<char> = Vec::new();
     let mut trunc: Vec<u8> = Vec::new();
     let mut bytes_cnt = 0;
@@ -319,33 +650,6 @@ fn parse_utf8(_raw: &[u8], prev_trunc: &Vec<u8>) -> (Vec<char>, Vec<u8>) {
     (res, trunc)
 }
 
-/// read characters
-///
-/// returns
-///  either a vector of characters or an Opcode, along with trailing truncated bytes for the next
-///  parsing
-pub fn read_chars_or_op(prev_trunc: &Vec<u8>) -> (Option<Vec<char>>, Vec<u8>, Op) {
-    let mut raw = [0_u8; 256];
-    let mut _stdin = stdin();
-    _stdin.read(&mut raw).expect("Failed to read");
-    let (char_vec, trunc) = parse_utf8(&mut raw, prev_trunc);
-    if char_vec.is_empty() {
-        return (None, trunc, Op::Noop);
-    }
-    if char_vec[0] as usize == 27 {
-        if char_vec.len() >= 3 && char_vec[1] as usize == 91 {
-            match char_vec[2] as usize {
-                65 => return (None, trunc, Op::Up),
-                66 => return (None, trunc, Op::Down),
-                67 => return (None, trunc, Op::Right),
-                68 => return (None, trunc, Op::Left),
-                _ => {}
-            };
-        }
-    }
-    (Some(char_vec), trunc, Op::Noop)
-}
-
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -531,6 +835,78 @@ pub mod test {
         assert_eq!(get_theme(Some(&conf)), target);
     }
 
+    #[test]
+    fn test_get_config() {
+        let (conf, _file) = mktemp_conf();
+        let mut file = _file.expect("failed to create temp file");
+        let _cf = CleanupFile { file: conf.clone() };
+        let _ = file.write(b" Theme = dark \n");
+        assert_eq!(get_config("theme", Some(&conf)).as_deref(), Some("dark"));
+        assert_eq!(get_config("o", Some(&conf)), None);
+        assert_eq!(get_search_mode(Some(&conf)), SearchMode::Regex);
+        let _ = file.write(b"search = glob\n");
+        assert_eq!(get_search_mode(Some(&conf)), SearchMode::Glob);
+        assert_eq!(get_scrolloff(Some(&conf)), 0);
+        let _ = file.write(b"scrolloff = 3\n");
+        assert_eq!(get_scrolloff(Some(&conf)), 3);
+        assert_eq!(get_columns(Some(&conf)), [6, 4]);
+        let _ = file.write(b"columns = 1, 3,4\n");
+        assert_eq!(get_columns(Some(&conf)), [1, 3, 4]);
+    }
+
+    #[test]
+    fn test_process_input() {
+        let ops = |keys: &str| {
+            let mut pending = Pending::default();
+            keys.chars()
+                .map(|c| process_input(Key::Char(c), &mut pending))
+                .filter(|(op, _)| *op != Op::Noop)
+                .collect::<Vec<(Op, Option<usize>)>>()
+        };
+        assert!(ops("j12k0") == [(Op::Down, None), (Op::Up, Some(12))]);
+        assert!(
+            ops("gg5ggmaGgj")
+                == [
+                    (Op::Top, None),
+                    (Op::Top, Some(5)),
+                    (Op::SetBookmark('a'), None),
+                    (Op::Bottom, None),
+                    (Op::Down, None)
+                ]
+        );
+        assert!(
+            ops("3ztzzzbzfZ2fxM`")
+                == [
+                    (Op::ScrollTop, Some(3)),
+                    (Op::ScrollCenter, None),
+                    (Op::ScrollBottom, None),
+                    (Op::Frecency, None),
+                    (Op::FindChar('x'), Some(2)),
+                    (Op::ScreenMiddle, None),
+                    (Op::Bookmarks, None)
+                ]
+        );
+        assert!(
+            ops("gn2gtgTgcyyddpdpp")
+                == [
+                    (Op::NewTab, None),
+                    (Op::NextTab, Some(2)),
+                    (Op::PrevTab, None),
+                    (Op::CloseTab, None),
+                    (Op::Copy, None),
+                    (Op::Cut, None),
+                    (Op::Paste, None)
+                ]
+        );
+        let mut pending = Pending::default();
+        assert!(process_input(Key::Ctrl('w'), &mut pending) == (Op::DualPane, None));
+        assert!(process_input(Key::F(6), &mut pending) == (Op::MoveToOther, None));
+        assert!(process_input(Key::Ctrl('n'), &mut pending) == (Op::JumpForward, None));
+        assert!(ops("tR") == [(Op::Tree, None), (Op::Reload, None)]);
+        // a count is dropped by a key that isn't a command
+        assert!(ops("4xj") == [(Op::Down, None)]);
+    }
+
     #[test]
     fn test_get_opener() {
         let (conf, _file) = mktemp_conf();
@@ -546,6 +922,38 @@ pub mod test {
             args.expect("failed to get args for assertion"),
             ["-R", "-es", "-m", "-b", "-A", "-V", "-D", "-q", &conf]
         );
+        // enter has none of its own, the editor is taken, keys are read like every other one
+        let _ = file.write(b"\n Editor = code -w\n");
+        let (comm, args) = get_opener(Op::ExitCursorEnter, Some(&conf));
+        assert_eq!(comm, "code");
+        assert_eq!(args.expect("failed to get args for assertion"), ["-w"]);
+    }
+
+    #[test]
+    fn test_write_paths() {
+        let paths = [PathBuf::from("/tmp/a b"), PathBuf::from("/tmp/c\nd")];
+        let mut out: Vec<u8> = Vec::new();
+        write_paths(&mut out, &paths, false).expect("failed to write paths");
+        assert_eq!(out, b"/tmp/a b\n/tmp/c\nd\n");
+        out.clear();
+        write_paths(&mut out, &paths, true).expect("failed to write paths");
+        assert_eq!(out, b"/tmp/a b\0/tmp/c\nd\0");
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let (_, _, root_dir, _cd) = random_dir_wcontent();
+        let path = PathBuf::from(format!("/tmp/{}/state/file", root_dir));
+        write_atomic(&path, b"old").expect("failed to write");
+        write_atomic(&path, b"new").expect("failed to overwrite");
+        assert_eq!(std::fs::read(&path).expect("failed to read"), b"new");
+        assert_eq!(path.parent().unwrap().read_dir().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_json_str() {
+        assert_eq!(json_str("/tmp/ts"), "\"/tmp/ts\"");
+        assert_eq!(json_str("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
     }
 
     #[test]
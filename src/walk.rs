@@ -0,0 +1,293 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use regex_lite::Regex;
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// A line of a .gitignore
+struct Rule {
+    re: Regex,
+    // !pattern, un-ignores what an earlier rule ignored
+    negate: bool,
+    // pattern/, only matches directories
+    dir_only: bool,
+}
+
+/// Rules of the ignore files in one directory, they match paths relative to it
+struct Ignore {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+/// Translate a gitignore glob to a regex, '*' and '?' don't match '/' while '**' does
+pub fn glob_to_regex(glob: &str) -> String {
+    let chars = glob.chars().collect::<Vec<char>>();
+    let mut re = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    // a/**/b matches a/b as well
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    re.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => match chars[i..].iter().position(|c| *c == ']') {
+                Some(end) if end > 1 => {
+                    let class = chars[i + 1..i + end].iter().collect::<String>();
+                    let class = match class.strip_prefix('!') {
+                        Some(negated) => format!("^{}", negated),
+                        None => class,
+                    };
+                    re.push('[');
+                    re.push_str(&class.replace('\\', "\\\\"));
+                    re.push(']');
+                    i += end + 1;
+                    continue;
+                }
+                _ => re.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                re.push_str(&regex_lite::escape(&chars[i + 1].to_string()));
+                i += 1;
+            }
+            c => re.push_str(&regex_lite::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    re
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negate, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        // with a slash the pattern is relative to the directory of the ignore file, otherwise it
+        // matches the name at any depth
+        let re = if line.contains('/') {
+            format!("^{}$", glob_to_regex(line.trim_start_matches('/')))
+        } else {
+            format!("(?:^|/){}$", glob_to_regex(line))
+        };
+        Some(Rule {
+            re: Regex::new(&re).ok()?,
+            negate,
+            dir_only,
+        })
+    }
+}
+
+impl Ignore {
+    /// Rules of the ignore files in dir, None if it has none
+    fn load(dir: &Path) -> Option<Ignore> {
+        let mut rules = Vec::new();
+        for file in IGNORE_FILES {
+            if let Ok(content) = read_to_string(dir.join(file)) {
+                rules.extend(content.lines().filter_map(Rule::parse));
+            }
+        }
+        if rules.is_empty() {
+            return None;
+        }
+        Some(Ignore {
+            base: dir.to_path_buf(),
+            rules,
+        })
+    }
+
+    /// Some(true) if the path is ignored, Some(false) if it's explicitly not, None if no rule
+    /// matches it
+    fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?.to_string_lossy();
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.re.is_match(&relative))
+            .map(|rule| !rule.negate)
+    }
+}
+
+/// The deeper ignore files override the ones above
+fn is_ignored(ignores: &[Ignore], path: &Path, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .find_map(|ignore| ignore.matches(path, is_dir))
+        .unwrap_or(false)
+}
+
+fn walk_dir(
+    dir: &Path,
+    ignores: &mut Vec<Ignore>,
+    cancel: &AtomicBool,
+    visit: &mut dyn FnMut(&Path, bool),
+) {
+    let pushed = match Ignore::load(dir) {
+        Some(ignore) => {
+            ignores.push(ignore);
+            true
+        }
+        None => false,
+    };
+    let mut entries = match read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|e| Some((e.path(), e.file_type().ok()?.is_dir())))
+            .collect::<Vec<(PathBuf, bool)>>(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+    for (path, is_dir) in entries {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name == ".git" {
+            continue;
+        }
+        if is_ignored(ignores, &path, is_dir) {
+            continue;
+        }
+        visit(&path, is_dir);
+        // symbolic links are not followed, there could be loops
+        if is_dir {
+            walk_dir(&path, ignores, cancel, visit);
+        }
+    }
+    if pushed {
+        ignores.pop();
+    }
+}
+
+/// Visit everything under root, depth first, skipping what .gitignore and .ignore files ignore.
+/// The ignore files of the parent directories count as well, up to the root of the git repo.
+/// .git is skipped. Stops early once cancel is set.
+pub fn walk(root: &Path, cancel: &AtomicBool, visit: &mut dyn FnMut(&Path, bool)) {
+    let mut ignores = Vec::new();
+    let mut dir = root.parent();
+    if !root.join(".git").exists() {
+        while let Some(parent) = dir {
+            if let Some(ignore) = Ignore::load(parent) {
+                ignores.push(ignore);
+            }
+            if parent.join(".git").exists() {
+                break;
+            }
+            dir = parent.parent();
+        }
+        // the outer ones come first
+        ignores.reverse();
+        if dir.is_none() {
+            // not in a git repo, the ignore files above don't apply
+            ignores.clear();
+        }
+    }
+    walk_dir(root, &mut ignores, cancel, visit);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::CleanupDir;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn test_glob_to_regex() {
+        let is_match = |glob: &str, path: &str| {
+            Regex::new(&format!("^{}$", glob_to_regex(glob)))
+                .unwrap()
+                .is_match(path)
+        };
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(!is_match("*.rs", "src/main.rs"));
+        assert!(is_match("src/**/*.rs", "src/main.rs"));
+        assert!(is_match("src/**/*.rs", "src/a/b/main.rs"));
+        assert!(is_match("target/**", "target/debug/ts"));
+        assert!(is_match("file?.[ch]", "file1.c"));
+        assert!(!is_match("file?.[!ch]", "file1.c"));
+        assert!(is_match("a+b(1).txt", "a+b(1).txt"));
+    }
+
+    #[test]
+    fn test_walk() {
+        let root = PathBuf::from("/tmp/ts-test-walk");
+        let _cd = CleanupDir {
+            dir: root.to_string_lossy().to_string(),
+        };
+        for dir in ["src/a", "target/debug", ".hidden", ".git", "logs"] {
+            create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "src/main.rs",
+            "src/a/lib.rs",
+            "src/a/gen.rs",
+            "target/debug/ts",
+            ".hidden/x",
+            ".git/HEAD",
+            "logs/1.log",
+            "logs/keep.log",
+            "README",
+        ] {
+            write(root.join(file), b"").unwrap();
+        }
+        write(root.join(".gitignore"), b"/target/\n*.log\n!keep.log\n").unwrap();
+        write(root.join("src/.ignore"), b"a/gen.rs\n").unwrap();
+
+        let mut found = Vec::new();
+        walk(&root, &AtomicBool::new(false), &mut |path, _| {
+            found.push(
+                path.strip_prefix(&root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string(),
+            )
+        });
+        assert_eq!(
+            found,
+            [
+                ".gitignore",
+                ".hidden",
+                ".hidden/x",
+                "README",
+                "logs",
+                "logs/keep.log",
+                "src",
+                "src/.ignore",
+                "src/a",
+                "src/a/lib.rs",
+                "src/main.rs"
+            ]
+        );
+
+        let mut found = 0;
+        let cancel = AtomicBool::new(true);
+        walk(&root, &cancel, &mut |_, _| found += 1);
+        assert_eq!(found, 0);
+    }
+}
@@ -0,0 +1,137 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use libc::{c_void, close, inotify_add_watch, inotify_init1, inotify_rm_watch, read};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+// what changes a listing: entries created, deleted or renamed, and the directory itself going away
+const MASK: u32 = libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVE_SELF
+    | libc::IN_ONLYDIR;
+
+// wd, mask, cookie and len of struct inotify_event, the name follows
+const EVENT_HEADER: usize = 16;
+
+/// Directories watched with inotify for changes
+pub struct Watcher {
+    fd: i32,
+    // watch descriptors and their directories
+    watches: Vec<(i32, PathBuf)>,
+}
+
+impl Watcher {
+    /// None when inotify isn't there, e.g. out of instances
+    pub fn new() -> Option<Watcher> {
+        let fd = unsafe { inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        Some(Watcher {
+            fd,
+            watches: Vec::new(),
+        })
+    }
+
+    /// Watch these directories and only these, the ones watched already are kept. A directory
+    /// that can't be watched, e.g. on a file system without inotify, is left out.
+    pub fn watch(&mut self, dirs: &[PathBuf]) {
+        let fd = self.fd;
+        self.watches.retain(|(wd, dir)| {
+            let keep = dirs.contains(dir);
+            if !keep {
+                unsafe { inotify_rm_watch(fd, *wd) };
+            }
+            keep
+        });
+        for dir in dirs {
+            if self.watches.iter().any(|(_, watched)| watched == dir) {
+                continue;
+            }
+            let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+                continue;
+            };
+            let wd = unsafe { inotify_add_watch(self.fd, path.as_ptr(), MASK) };
+            if wd >= 0 {
+                self.watches.push((wd, dir.clone()));
+            }
+        }
+    }
+
+    /// The inotify fd, readable when there are events
+    pub fn fd(&self) -> i32 {
+        self.fd
+    }
+
+    /// Read the events without waiting
+    ///
+    /// returns
+    ///  whether any of the watched directories changed
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        let mut buf = [0_u8; 4096];
+        loop {
+            let len = unsafe { read(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+            if len <= 0 {
+                return changed;
+            }
+            let mut start = 0;
+            while start + EVENT_HEADER <= len as usize {
+                let field = |i: usize| {
+                    let at = start + i * 4;
+                    u32::from_ne_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+                };
+                let (wd, mask, name_len) = (field(0) as i32, field(1), field(3) as usize);
+                // IN_IGNORED follows a watch that's removed
+                if mask & libc::IN_IGNORED == 0 && self.watches.iter().any(|(w, _)| *w == wd) {
+                    changed = true;
+                }
+                start += EVENT_HEADER + name_len;
+            }
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        unsafe { close(self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test::random_dir_wcontent;
+    use std::fs::{remove_file, File};
+
+    #[test]
+    fn test_changed() {
+        let (files, dirs, root_dir, _cd) = random_dir_wcontent();
+        let root = PathBuf::from(format!("/tmp/{}", root_dir));
+        let mut watcher = Watcher::new().expect("no inotify");
+        watcher.watch(&[root.clone(), root.join(&dirs[0])]);
+        assert!(!watcher.changed());
+
+        File::create(root.join("new")).expect("failed to create file");
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+        remove_file(root.join(&files[0])).expect("failed to remove file");
+        File::create(root.join(&dirs[0]).join("new")).expect("failed to create file");
+        assert!(watcher.changed());
+
+        // a directory no longer watched doesn't count
+        watcher.watch(&[root.join(&dirs[0])]);
+        File::create(root.join("newer")).expect("failed to create file");
+        assert!(!watcher.changed());
+    }
+}
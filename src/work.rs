@@ -0,0 +1,254 @@
+/*═══════════════════════════════════════════════════════════════════════╗
+║                         (C)  Howard Chu                                ║
+║                                                                        ║
+║ Permission to use, copy, modify, and/or distribute this software for   ║
+║ any purpose with or without fee is hereby granted, provided that the   ║
+║ above copyright notice and this permission notice appear in all copies ║
+╚═══════════════════════════════════════════════════════════════════════*/
+
+use crate::event::Waker;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Work done on another thread, such as reading a directory or copying files. The event loop is
+/// woken up when it's done, or for each part of it a streaming job gives as it goes. Dropping the
+/// job cancels it, a job cancelled but kept is still waited for, for what it did before it
+/// stopped.
+pub struct Job<T> {
+    rx: Receiver<T>,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Where a streaming job sends what it's done so far
+pub struct Stream<T> {
+    tx: Sender<T>,
+    waker: Option<Waker>,
+}
+
+impl<T> Stream<T> {
+    /// Send a part of the result and wake up the event loop
+    ///
+    /// returns
+    ///  false if nobody's waiting for it, the job's been dropped
+    pub fn send(&self, part: T) -> bool {
+        if self.tx.send(part).is_err() {
+            return false;
+        }
+        if let Some(waker) = &self.waker {
+            waker.wake();
+        }
+        true
+    }
+}
+
+impl<T: Send + 'static> Job<T> {
+    /// Start the work on a thread, it should give up when the flag it's given is set
+    pub fn spawn<F>(waker: Option<Waker>, work: F) -> Job<T>
+    where
+        F: FnOnce(&AtomicBool) -> T + Send + 'static,
+    {
+        Job::stream(waker, move |cancel, stream| {
+            stream.send(work(cancel));
+        })
+    }
+
+    /// Start work that sends its result a part at a time, it's done when it returns
+    pub fn stream<F>(waker: Option<Waker>, work: F) -> Job<T>
+    where
+        F: FnOnce(&AtomicBool, &Stream<T>) + Send + 'static,
+    {
+        let (tx, rx) = channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let work_cancel = cancel.clone();
+        thread::spawn(move || {
+            let stream = Stream { tx, waker };
+            work(&work_cancel, &stream);
+            // the channel is closed when it's done, nobody's waiting for a dropped job
+            let Stream { tx, waker } = stream;
+            drop(tx);
+            if let Some(waker) = waker.filter(|_| !work_cancel.load(Ordering::Relaxed)) {
+                waker.wake();
+            }
+        });
+        Job { rx, cancel }
+    }
+}
+
+impl<T> Job<T> {
+    /// Wait for at most timeout for the work to be done, zero doesn't wait
+    ///
+    /// returns
+    ///  the result, None if it isn't done or it was cancelled
+    pub fn wait(&self, timeout: Duration) -> Option<T> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    /// Wait for at most timeout for the next part from a streaming job, zero doesn't wait
+    ///
+    /// returns
+    ///  the part, Timeout if there's none yet, Disconnected once it's all been taken
+    pub fn next(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.rx.recv_timeout(timeout)
+    }
+
+    /// Tell the work to stop, it's still waited for
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<T> Drop for Job<T> {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A thread kept for one kind of work, such as reading the preview, which is asked for again and
+/// again. A request made while it's busy replaces the one waiting, and only the answer to the
+/// last request is taken, so it never falls behind. The thread ends when the worker is dropped.
+pub struct Worker<Q, A> {
+    tx: Sender<(u64, Q)>,
+    rx: Receiver<(u64, A)>,
+    // the number of the last request
+    last: u64,
+    // the last request hasn't been answered yet
+    pub busy: bool,
+}
+
+impl<Q: Send + 'static, A: Send + 'static> Worker<Q, A> {
+    /// Start the thread, it answers each request with work
+    pub fn start<F>(waker: Option<Waker>, mut work: F) -> Worker<Q, A>
+    where
+        F: FnMut(Q) -> A + Send + 'static,
+    {
+        let (tx, requests) = channel::<(u64, Q)>();
+        let (answers, rx) = channel();
+        thread::spawn(move || {
+            while let Ok(mut request) = requests.recv() {
+                while let Ok(later) = requests.try_recv() {
+                    request = later;
+                }
+                let (n, request) = request;
+                if answers.send((n, work(request))).is_err() {
+                    return;
+                }
+                if let Some(waker) = &waker {
+                    waker.wake();
+                }
+            }
+        });
+        Worker {
+            tx,
+            rx,
+            last: 0,
+            busy: false,
+        }
+    }
+}
+
+impl<Q, A> Worker<Q, A> {
+    /// Ask for the work to be done, instead of what's asked before if it hasn't been started
+    pub fn request(&mut self, request: Q) {
+        self.last += 1;
+        self.busy = self.tx.send((self.last, request)).is_ok();
+    }
+
+    /// Wait for at most timeout for the answer to the last request, zero doesn't wait
+    ///
+    /// returns
+    ///  the answer, None if it isn't done or it's been taken
+    pub fn answer(&mut self, timeout: Duration) -> Option<A> {
+        let deadline = Instant::now() + timeout;
+        while self.busy {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.rx.recv_timeout(left) {
+                Ok((n, answer)) if n == self.last => {
+                    self.busy = false;
+                    return Some(answer);
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_job() {
+        let job = Job::spawn(None, |_| 42);
+        assert_eq!(job.wait(Duration::from_secs(5)), Some(42));
+
+        // a dropped job is told to stop
+        let stopped = Arc::new(AtomicUsize::new(0));
+        let job_stopped = stopped.clone();
+        let job = Job::spawn(None, move |cancel| {
+            while !cancel.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            job_stopped.store(1, Ordering::Relaxed);
+        });
+        assert_eq!(job.wait(Duration::from_millis(20)), None);
+        drop(job);
+        for _ in 0..5000 {
+            if stopped.load(Ordering::Relaxed) == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(stopped.load(Ordering::Relaxed), 1);
+
+        // a job cancelled but kept gives what it did
+        let job = Job::spawn(None, |cancel| {
+            let mut done = 0;
+            while !cancel.load(Ordering::Relaxed) {
+                done += 1;
+                thread::sleep(Duration::from_millis(1));
+            }
+            done
+        });
+        job.cancel();
+        assert!(job.wait(Duration::from_secs(5)).is_some());
+
+        // a streaming job gives its parts in order, then it's done
+        let job = Job::stream(None, |_, stream| {
+            for n in 0..3 {
+                stream.send(n);
+            }
+        });
+        for n in 0..3 {
+            assert_eq!(job.next(Duration::from_secs(5)), Ok(n));
+        }
+        assert_eq!(
+            job.next(Duration::from_secs(5)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn test_worker() {
+        let mut worker = Worker::start(None, |n: u64| {
+            thread::sleep(Duration::from_millis(20));
+            n * 2
+        });
+        assert_eq!(worker.answer(Duration::ZERO), None);
+        worker.request(1);
+        assert_eq!(worker.answer(Duration::from_secs(5)), Some(2));
+        assert!(!worker.busy);
+
+        // only the last request is answered
+        for n in 2..10 {
+            worker.request(n);
+        }
+        assert_eq!(worker.answer(Duration::from_secs(5)), Some(18));
+        assert_eq!(worker.answer(Duration::from_millis(50)), None);
+    }
+}